@@ -0,0 +1,339 @@
+use crate::structure::{parse_cell, Cell, Rule, Sudoku};
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+// +----------------+
+// | InequalityRule |
+// +----------------+
+
+// Smallest and largest value a cell may still hold, none for a dead
+// cell.
+fn bounds(c: &Cell) -> Option<(u8, u8)> {
+    if c.value() != 0 {
+        return Some((c.value(), c.value()));
+    }
+    let mask = c.options();
+    if mask == 0 {
+        return None;
+    }
+    Some((
+        mask.trailing_zeros() as u8 + 1,
+        16 - mask.leading_zeros() as u8,
+    ))
+}
+
+// Target cell's value must be less (or greater) than the other
+// cell's value.  Each comparison takes two of these rules, one for
+// each cell, as rules can only ever change the target.
+#[derive(Clone)]
+pub struct InequalityRule {
+    target: usize,
+    other: usize,
+    less: bool,
+}
+
+impl InequalityRule {
+    /// Value of target is less than value of other.
+    pub fn less(target: usize, other: usize) -> Self {
+        InequalityRule {
+            target,
+            other,
+            less: true,
+        }
+    }
+
+    /// Value of target is greater than value of other.
+    pub fn greater(target: usize, other: usize) -> Self {
+        InequalityRule {
+            target,
+            other,
+            less: false,
+        }
+    }
+}
+
+impl Rule for InequalityRule {
+    fn name(&self) -> &str {
+        "inequality"
+    }
+
+    fn predicate(&self, target: usize, other: usize) -> bool {
+        target == self.target && other == self.other
+    }
+
+    fn consider(&mut self, target: &mut Cell, other: Cell) {
+        if target.value() != 0 {
+            return;
+        }
+        // Dead cells have no bounds to pass on.
+        let (min, max) = match bounds(&other) {
+            Some(x) => x,
+            None => return,
+        };
+        if self.less {
+            target.shut_outside(1, max - 1);
+        } else {
+            target.shut_outside(min + 1, 9);
+        }
+    }
+}
+
+// +-----------+
+// | ChainRule |
+// +-----------+
+
+// Values strictly increase along a path of cells.  Both ends are
+// free, so the bounds of a cell come from how far it is from either
+// end and from the bounds of every other cell of the chain: the k-th
+// cell is at least j-th cell's minimum plus k-j, and at most a later
+// cell's maximum minus the distance between them.
+#[derive(Clone)]
+pub struct ChainRule {
+    cells: Vec<usize>,
+    // Bounds of the current target, tightened cell by cell.
+    lo: u8,
+    hi: u8,
+    position: Option<usize>,
+}
+
+impl ChainRule {
+    pub fn new(cells: Vec<usize>) -> Self {
+        ChainRule {
+            cells,
+            lo: 1,
+            hi: 9,
+            position: None,
+        }
+    }
+}
+
+impl Rule for ChainRule {
+    fn name(&self) -> &str {
+        "chain"
+    }
+
+    fn predicate(&self, target: usize, other: usize) -> bool {
+        target != other && self.cells.contains(&target) && self.cells.contains(&other)
+    }
+
+    fn begin_at(&mut self, target: usize) {
+        let n = self.cells.len();
+        self.position = self.cells.iter().position(|x| *x == target);
+        if let Some(k) = self.position {
+            self.lo = k as u8 + 1;
+            self.hi = 9u8.saturating_sub((n - 1 - k) as u8);
+        }
+    }
+
+    fn consider_at(&mut self, _: usize, _: &mut Cell, other_idx: usize, other: &Cell) {
+        let (k, j) = match (
+            self.position,
+            self.cells.iter().position(|x| *x == other_idx),
+        ) {
+            (Some(k), Some(j)) => (k as u8, j as u8),
+            _ => return,
+        };
+        // Dead cells have no bounds to pass on.
+        let (min, max) = match bounds(other) {
+            Some(x) => x,
+            None => return,
+        };
+        if j < k {
+            self.lo = self.lo.max(min + (k - j));
+        } else {
+            self.hi = self.hi.min(max.saturating_sub(j - k));
+        }
+    }
+
+    fn end(&self, target: &mut Cell) {
+        if self.position.is_none() || target.value() != 0 {
+            return;
+        }
+        target.shut_outside(self.lo, self.hi);
+    }
+}
+
+// +--------+
+// | Sudoku |
+// +--------+
+
+#[derive(Debug, PartialEq)]
+pub enum InequalityError {
+    // Token that isn't a valid cell reference, or an odd one out.
+    Parse(String),
+    // Cells aren't orthogonal neighbours.
+    NotAdjacent(usize, usize),
+    // Cells are neighbours, but in different squares.
+    DifferentSquares(usize, usize),
+}
+
+/// Parse a list of comparisons written as pairs of "rXcY" tokens,
+/// smaller cell first, e.g. "r1c1 r1c2  r2c1 r1c1".
+pub fn parse_inequalities(s: &str) -> Result<Vec<(usize, usize)>, InequalityError> {
+    let cells = s
+        .split_whitespace()
+        .map(|x| parse_cell(x).ok_or_else(|| InequalityError::Parse(x.to_string())))
+        .collect::<Result<Vec<usize>, InequalityError>>()?;
+    if cells.len() % 2 != 0 {
+        return Err(InequalityError::Parse(s.to_string()));
+    }
+    Ok(cells.chunks(2).map(|x| (x[0], x[1])).collect())
+}
+
+impl Sudoku {
+    /// Require the value of cell smaller to be less than the value of
+    /// cell larger.  Only neighbours within the same square can be
+    /// compared.
+    pub fn add_inequality(&mut self, smaller: usize, larger: usize) -> Result<(), InequalityError> {
+        let (a, b) = (smaller as i32, larger as i32);
+        let dr = (a / 9 - b / 9).abs();
+        let dc = (a % 9 - b % 9).abs();
+        if a >= 81 || b >= 81 || dr + dc != 1 {
+            return Err(InequalityError::NotAdjacent(smaller, larger));
+        }
+        let square = |i: i32| (i / 27, i % 9 / 3);
+        if square(a) != square(b) {
+            return Err(InequalityError::DifferentSquares(smaller, larger));
+        }
+        self.rules
+            .push(Box::new(InequalityRule::less(smaller, larger)));
+        self.rules
+            .push(Box::new(InequalityRule::greater(larger, smaller)));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::presets;
+    use crate::structure::Solver;
+
+    #[test]
+    fn test_parse_inequalities() {
+        let have = parse_inequalities("r1c1 r1c2\n r2c1 r1c1").unwrap();
+        assert_eq!(have, vec![(0, 1), (9, 0)]);
+        assert!(parse_inequalities("r1c1").is_err());
+        assert!(parse_inequalities("r1c1 x").is_err());
+    }
+
+    #[test]
+    fn test_add_inequality_invalid() {
+        let mut s = Sudoku::default();
+        assert_eq!(
+            s.add_inequality(0, 2),
+            Err(InequalityError::NotAdjacent(0, 2))
+        );
+        assert_eq!(
+            s.add_inequality(0, 10),
+            Err(InequalityError::NotAdjacent(0, 10))
+        );
+        assert_eq!(
+            s.add_inequality(8, 9),
+            Err(InequalityError::NotAdjacent(8, 9))
+        );
+        assert_eq!(
+            s.add_inequality(2, 3),
+            Err(InequalityError::DifferentSquares(2, 3))
+        );
+        assert_eq!(
+            s.add_inequality(18, 27),
+            Err(InequalityError::DifferentSquares(18, 27))
+        );
+        assert!(s.rules.is_empty());
+        assert_eq!(s.add_inequality(0, 1), Ok(()));
+        assert_eq!(s.rules.len(), 2);
+    }
+
+    #[test]
+    fn test_inequality_row() {
+        // Comparison puzzles never cross squares, but the rules
+        // themselves don't care.
+        let mut s = Sudoku::default().with_standard_rules();
+        for i in 0..8 {
+            s.rules.push(Box::new(InequalityRule::less(i, i + 1)));
+            s.rules.push(Box::new(InequalityRule::greater(i + 1, i)));
+        }
+        assert_eq!(s.solve(), 9);
+        for i in 0..9 {
+            assert_eq!(s.cells[i].value(), i as u8 + 1, "i={}", i);
+        }
+    }
+
+    #[test]
+    fn test_inequality_chain() {
+        // Chains can't cross squares, so use a snake through the top
+        // left square: 0 < 1 < 2 < 11 < 10 < 9 < 18 < 19 < 20.
+        let mut s = Sudoku::default().with_standard_rules();
+        let snake = [0, 1, 2, 11, 10, 9, 18, 19, 20];
+        for x in snake.windows(2) {
+            s.add_inequality(x[0], x[1]).unwrap();
+        }
+        assert_eq!(s.solve(), 9);
+        for (v, &index) in snake.iter().enumerate() {
+            assert_eq!(s.cells[index].value(), v as u8 + 1, "index={}", index);
+        }
+    }
+
+    #[test]
+    fn test_chain_filled_middle() {
+        // 0 < 1 < 2 < 3 with a 5 in cell 1: cell 0 drops to 1-4, and
+        // cells 2 and 3 climb to 6-8 and 7-9.
+        let mut s = Sudoku::default();
+        s.cells[1].set(5);
+        s.rules.push(Box::new(ChainRule::new(vec![0, 1, 2, 3])));
+        s.solve_step();
+        assert_eq!(s.cells[0].options(), 0b0_0000_1111);
+        assert_eq!(s.cells[2].options(), 0b0_1110_0000);
+        assert_eq!(s.cells[3].options(), 0b1_1100_0000);
+        assert_eq!(s.cells[4].num_options(), 9);
+    }
+
+    #[test]
+    fn test_chain_both_ends() {
+        // Nothing pins the first cell, a 3 third in line leaves only
+        // 1 and 2 before it.
+        let mut s = Sudoku::default();
+        s.cells[20].set(3);
+        s.rules.push(Box::new(ChainRule::new(vec![0, 10, 20, 30])));
+        s.solve();
+        assert_eq!(s.cells[0].value(), 1);
+        assert_eq!(s.cells[10].value(), 2);
+        assert_eq!(s.cells[30].options(), 0b1_1111_1000);
+    }
+
+    #[test]
+    fn test_inequality_dead_other() {
+        // An unsolved cell without options, the way search leaves
+        // them, has no bounds to compare with.
+        let mut s = Sudoku::default();
+        for v in 1u8..=9 {
+            s.cells[9].shut(v);
+        }
+        s.add_inequality(0, 9).unwrap();
+        s.solve_step();
+        assert_eq!(s.cells[0].num_options(), 9);
+
+        // Search runs into dead cells on this board, some of the
+        // constraints contradicting the givens.
+        let mut s = Sudoku::random_puzzle(6, 30).with_standard_rules();
+        for &a in [0, 1, 2, 30, 31, 32].iter() {
+            s.add_inequality(a, a + 9).unwrap();
+        }
+        if let Some(x) = s.solved() {
+            assert!(x.is_solved() && x.is_valid());
+        }
+        s.count_solutions(3);
+    }
+
+    #[test]
+    fn test_comparison() {
+        let mut s = presets::load_comparison().with_standard_rules();
+        assert!(s.cells.iter().all(|x| x.value() == 0));
+        s.solve();
+        assert!(s.is_solved());
+    }
+}
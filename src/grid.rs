@@ -0,0 +1,502 @@
+use crate::rules::{ExclusionRule, SingleOptRule};
+use crate::structure::{step_cells, Cell, ParseSudokuError, Rule, StepResult, Sudoku, Unit};
+use alloc::{boxed::Box, format, string::String, vec, vec::Vec};
+use core::fmt::{Display, Error, Formatter};
+
+// +------------+
+// | Dimensions |
+// +------------+
+
+/// Geometry of a grid: size x size cells split into boxes of
+/// box_rows x box_cols cells.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Dimensions {
+    pub size: usize,
+    pub box_rows: usize,
+    pub box_cols: usize,
+}
+
+impl Dimensions {
+    /// Grids are at most 16x16.
+    pub fn new(box_rows: usize, box_cols: usize) -> Option<Dimensions> {
+        let size = box_rows * box_cols;
+        if (1..=16).contains(&size) {
+            Some(Dimensions {
+                size,
+                box_rows,
+                box_cols,
+            })
+        } else {
+            None
+        }
+    }
+
+    pub fn four() -> Dimensions {
+        Dimensions::new(2, 2).unwrap()
+    }
+
+    pub fn six() -> Dimensions {
+        Dimensions::new(2, 3).unwrap()
+    }
+
+    pub fn nine() -> Dimensions {
+        Dimensions::new(3, 3).unwrap()
+    }
+
+    pub fn sixteen() -> Dimensions {
+        Dimensions::new(4, 4).unwrap()
+    }
+
+    pub fn len(&self) -> usize {
+        self.size * self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    pub fn same_row(&self, a: usize, b: usize) -> bool {
+        a / self.size == b / self.size
+    }
+
+    pub fn same_col(&self, a: usize, b: usize) -> bool {
+        a % self.size == b % self.size
+    }
+
+    pub fn same_box(&self, a: usize, b: usize) -> bool {
+        self.box_of(a) == self.box_of(b)
+    }
+
+    /// Both cells are in the same row, column or box, for
+    /// Unit::Square.
+    pub fn contains(&self, unit: Unit, a: usize, b: usize) -> bool {
+        match unit {
+            Unit::Row => self.same_row(a, b),
+            Unit::Col => self.same_col(a, b),
+            Unit::Square => self.same_box(a, b),
+        }
+    }
+
+    /// Boxes are numbered left to right, top to bottom.
+    pub fn box_of(&self, index: usize) -> usize {
+        let row = index / self.size / self.box_rows;
+        let col = index % self.size / self.box_cols;
+        row * (self.size / self.box_cols) + col
+    }
+
+    /// All rows, then all columns, then all boxes.
+    pub fn units(&self) -> Vec<Vec<usize>> {
+        let n = self.size;
+        let mut units = vec![];
+        for r in 0..n {
+            units.push((0..n).map(|c| r * n + c).collect());
+        }
+        for c in 0..n {
+            units.push((0..n).map(|r| r * n + c).collect());
+        }
+        for b in 0..n {
+            units.push((0..self.len()).filter(|i| self.box_of(*i) == b).collect());
+        }
+        units
+    }
+}
+
+// +------+
+// | Grid |
+// +------+
+
+/// A Sudoku of any supported size, solved by the same rules and
+/// passes as Sudoku.  Rules made with for_grid() know the size; the
+/// 9x9 ones only fit a 9x9 grid.
+pub struct Grid {
+    dims: Dimensions,
+    cells: Vec<Cell>,
+    pub rules: Vec<Box<dyn Rule>>,
+}
+
+impl Grid {
+    /// Empty grid without rules.
+    pub fn new(dims: Dimensions) -> Grid {
+        Grid {
+            dims,
+            cells: vec![Cell::with_size(dims.size as u8); dims.len()],
+            rules: vec![],
+        }
+    }
+
+    /// Exclusion and single option rules for the rows, columns and
+    /// boxes.
+    pub fn with_standard_rules(mut self) -> Self {
+        for unit in [Unit::Row, Unit::Col, Unit::Square] {
+            self.rules
+                .push(Box::new(ExclusionRule::for_grid(self.dims, unit)));
+        }
+        for unit in [Unit::Row, Unit::Col, Unit::Square] {
+            self.rules
+                .push(Box::new(SingleOptRule::for_grid(self.dims, unit)));
+        }
+        self
+    }
+
+    pub fn dims(&self) -> Dimensions {
+        self.dims
+    }
+
+    pub fn cells(&self) -> &[Cell] {
+        &self.cells
+    }
+
+    pub fn value(&self, index: usize) -> u8 {
+        self.cells[index].value()
+    }
+
+    pub fn set(&mut self, index: usize, value: u8) {
+        assert!(1 <= value && value as usize <= self.dims.size);
+        self.cells[index].set(value);
+    }
+
+    pub fn is_open(&self, index: usize, value: u8) -> bool {
+        self.cells[index].is_open(value)
+    }
+
+    /// One pass of every rule over every target, the same as
+    /// Sudoku::step().
+    pub fn step(&mut self) -> StepResult {
+        step_cells(&mut self.rules, &mut self.cells, &mut |_| ())
+    }
+
+    /// Run passes until the grid is solved or a pass changes nothing,
+    /// the same as Sudoku::passes(), and return the number of newly
+    /// solved cells.  Not Solver::solve(), a 16x16 grid has more
+    /// cells than a u8 counts.
+    pub fn solve(&mut self) -> usize {
+        let mut sum = 0;
+        while !self.is_solved() {
+            let step = self.step();
+            sum += step.placed;
+            if step.changed.is_empty() {
+                break;
+            }
+        }
+        sum
+    }
+
+    pub fn is_solved(&self) -> bool {
+        self.cells.iter().all(|x| x.value() != 0)
+    }
+
+    /// No value appears twice in a row, column or box.
+    pub fn is_valid(&self) -> bool {
+        self.dims.units().iter().all(|unit| {
+            let mut seen = 0u32;
+            unit.iter()
+                .map(|i| self.cells[*i].value())
+                .filter(|v| *v != 0)
+                .all(|v| {
+                    let fresh = seen & 1 << (v - 1) == 0;
+                    seen |= 1 << (v - 1);
+                    fresh
+                })
+        })
+    }
+
+    /// Parse values left to right, top to bottom.  Up to 9x9, digits
+    /// are values, '0', '.' and '_' are empty cells and everything else
+    /// is ignored.  Larger grids need more than one digit per value,
+    /// so values are whitespace separated numbers instead, which is
+    /// what Display produces.
+    pub fn parse(dims: Dimensions, s: &str) -> Result<Grid, ParseSudokuError> {
+        let xs: Vec<u8> = if dims.size <= 9 {
+            s.chars()
+                .filter_map(|x| match x {
+                    '.' | '_' => Some(0),
+                    _ => x.to_digit(10).map(|x| x as u8),
+                })
+                .collect()
+        } else {
+            s.split(|x: char| x.is_whitespace() || x == '|')
+                .filter(|x| !x.is_empty() && !x.starts_with('-'))
+                .map(|x| match x {
+                    "." | "_" => Ok(0),
+                    _ => x.parse().map_err(|_| ParseSudokuError {}),
+                })
+                .collect::<Result<Vec<u8>, ParseSudokuError>>()?
+        };
+        Self::from_values(dims, &xs)
+    }
+
+    /// Parse a 16x16 grid in hex notation, where '0' to 'F' stand for
+    /// values 1 to 16, '.' and '_' are empty cells and everything else
+    /// is ignored.
+    pub fn parse_hex(s: &str) -> Result<Grid, ParseSudokuError> {
+        let xs: Vec<u8> = s
+            .chars()
+            .filter_map(|x| match x {
+                '.' | '_' => Some(0),
+                _ => x.to_digit(16).map(|x| x as u8 + 1),
+            })
+            .collect();
+        Self::from_values(Dimensions::sixteen(), &xs)
+    }
+
+    fn from_values(dims: Dimensions, xs: &[u8]) -> Result<Grid, ParseSudokuError> {
+        if xs.len() != dims.len() || xs.iter().any(|x| *x as usize > dims.size) {
+            return Err(ParseSudokuError {});
+        }
+        let mut grid = Grid::new(dims);
+        for (index, value) in xs.iter().enumerate().filter(|(_, x)| **x != 0) {
+            grid.set(index, *value);
+        }
+        Ok(grid)
+    }
+}
+
+impl Clone for Grid {
+    fn clone(&self) -> Self {
+        Grid {
+            dims: self.dims,
+            cells: self.cells.clone(),
+            rules: self.rules.iter().map(|x| x.duplicate()).collect(),
+        }
+    }
+}
+
+/// A 9x9 grid with the same cells and rules.
+impl From<&Sudoku> for Grid {
+    fn from(s: &Sudoku) -> Self {
+        Grid {
+            dims: Dimensions::nine(),
+            cells: s.cells.to_vec(),
+            rules: s.rules.iter().map(|x| x.duplicate()).collect(),
+        }
+    }
+}
+
+impl Display for Grid {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        // Same layout as Sudoku, e.g. for 6x6:
+        //  1 2 3 | 4 5 6
+        //  4 5 6 | 1 2 3
+        // -------+-------
+        let Dimensions {
+            size,
+            box_rows,
+            box_cols,
+        } = self.dims;
+        // Two digit values need wider columns and a visible empty
+        // cell to parse back.
+        let (width, empty) = if size > 9 { (2, ".") } else { (1, "") };
+        let segment = "-".repeat((width + 1) * box_cols + 1);
+        let separator = vec![segment; size / box_cols].join("+");
+        for (r, row) in self.cells.chunks(size).enumerate() {
+            if r != 0 {
+                writeln!(f)?;
+                if r % box_rows == 0 {
+                    writeln!(f, "{}", separator)?;
+                }
+            }
+            let mut line = String::new();
+            for (c, cell) in row.iter().enumerate() {
+                if c != 0 && c % box_cols == 0 {
+                    line.push_str(" |");
+                }
+                match cell.value() {
+                    0 => line.push_str(&format!(" {:>1$}", empty, width)),
+                    v => line.push_str(&format!(" {:>1$}", v, width)),
+                }
+            }
+            write!(f, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::presets;
+
+    fn values(g: &Grid) -> Vec<u8> {
+        g.cells().iter().map(Cell::value).collect()
+    }
+
+    #[test]
+    fn test_dimensions() {
+        assert_eq!(Dimensions::new(4, 5), None);
+        assert_eq!(Dimensions::new(3, 4).unwrap().size, 12);
+        assert_eq!(Dimensions::four().len(), 16);
+        assert_eq!(Dimensions::six().len(), 36);
+        assert_eq!(Dimensions::nine().units().len(), 27);
+    }
+
+    #[test]
+    fn test_six_boxes() {
+        // Boxes are two rows high and three columns wide:
+        //  0 0 0 | 1 1 1
+        //  0 0 0 | 1 1 1
+        // -------+-------
+        //  2 2 2 | 3 3 3
+        let d = Dimensions::six();
+        assert_eq!(d.box_of(0), 0);
+        assert_eq!(d.box_of(2), 0);
+        assert_eq!(d.box_of(3), 1);
+        assert_eq!(d.box_of(8), 0);
+        assert_eq!(d.box_of(12), 2);
+        assert_eq!(d.box_of(35), 5);
+        assert!(d.same_box(0, 8));
+        assert!(!d.same_box(0, 3));
+        assert!(!d.same_box(0, 12));
+        for unit in d.units() {
+            assert_eq!(unit.len(), 6);
+        }
+    }
+
+    #[test]
+    fn test_four() {
+        let mut g = Grid::parse(
+            Dimensions::four(),
+            "1 . | . 4
+             3 . | 1 .
+             ----+----
+             . 1 | . .
+             4 . | . 1",
+        )
+        .unwrap()
+        .with_standard_rules();
+        assert!(g.is_valid());
+        assert_eq!(g.solve(), 9);
+        assert!(g.is_solved());
+        assert!(g.is_valid());
+        let want = "
+ 1 2 | 3 4
+ 3 4 | 1 2
+-----+-----
+ 2 1 | 4 3
+ 4 3 | 2 1"
+            .strip_prefix('\n')
+            .unwrap();
+        assert_eq!(g.to_string(), want);
+    }
+
+    #[test]
+    fn test_six() {
+        let mut g = Grid::parse(
+            Dimensions::six(),
+            "
+            . . 3 | . 1 .
+            5 6 . | 3 2 .
+            ------+------
+            . 5 4 | 2 . 3
+            2 . 6 | 4 5 .
+            ------+------
+            . 1 2 | . 4 5
+            . 4 . | 1 . .",
+        )
+        .unwrap()
+        .with_standard_rules();
+        g.solve();
+        assert!(g.is_solved());
+        assert!(g.is_valid());
+        assert_eq!(values(&g)[..6], [4, 2, 3, 5, 1, 6]);
+    }
+
+    const HEX: &str = "
+        ...9 .2.. 0E.4 1..C
+        E.4. .... .BC1 .6.A
+        .F.. D0.. ..A. .8..
+        .... .F.. ..97 4E0.
+        .8.. 2... .... .1..
+        .6.. .... .... 9.E.
+        ..DF 0.49 6... .78.
+        .E.. 58.A .1.. C3.2
+        F.B. .... ..7. ....
+        .... .9.. ..3. 6...
+        5..4 ..26 D..E B.C3
+        .A.7 .C.. .548 .0..
+        ...6 ..D0 7..2 ....
+        A... 63.F .9.. .D1B
+        .45. 8... 1.B0 .C..
+        .... ..9. 3C.. 2...";
+
+    #[test]
+    fn test_sixteen() {
+        let mut g = Grid::parse_hex(HEX).unwrap().with_standard_rules();
+        assert_eq!(g.value(3), 10);
+        assert_eq!(g.value(8), 1);
+        assert_eq!(g.value(9), 15);
+        assert!(g.is_valid());
+
+        let empty = values(&g).iter().filter(|x| **x == 0).count();
+        assert_eq!(g.solve(), empty);
+        assert!(g.is_solved());
+        assert!(g.is_valid());
+        // Solution starts with 8579A2630ED41BFC.
+        assert_eq!(
+            values(&g)[..16],
+            [9, 6, 8, 10, 11, 3, 7, 4, 1, 15, 14, 5, 2, 12, 16, 13]
+        );
+    }
+
+    #[test]
+    fn test_sixteen_display() {
+        let g = Grid::parse_hex(HEX).unwrap();
+        let have = g.to_string();
+        let mut lines = have.lines();
+        assert_eq!(
+            lines.next(),
+            Some("  .  .  . 10 |  .  3  .  . |  1 15  .  5 |  2  .  . 13")
+        );
+        assert_eq!(
+            lines.nth(3),
+            Some("-------------+-------------+-------------+-------------")
+        );
+        assert_eq!(have.lines().count(), 19);
+        let parsed = Grid::parse(Dimensions::sixteen(), &have).unwrap();
+        assert_eq!(parsed.cells(), g.cells());
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(Grid::parse(Dimensions::four(), "1234").is_err());
+        assert!(Grid::parse(Dimensions::four(), "5... .... .... ....").is_err());
+        assert!(Grid::parse(Dimensions::sixteen(), &"17 ".repeat(256)).is_err());
+        assert!(Grid::parse(Dimensions::sixteen(), &"x ".repeat(256)).is_err());
+    }
+
+    #[test]
+    fn test_is_valid() {
+        let mut g = Grid::new(Dimensions::six());
+        g.set(0, 1);
+        g.set(8, 1);
+        assert!(!g.is_valid());
+    }
+
+    #[test]
+    fn test_solve_after_shutting() {
+        // The first pass only shuts options, the second places a cell.
+        let s = Sudoku::random_puzzle(0, 24).with_standard_rules();
+        let mut g = Grid::from(&s);
+        let first = g.step();
+        assert_eq!(first.placed, 0);
+        assert!(!first.changed.is_empty());
+
+        let mut g = Grid::from(&s);
+        let mut want = s;
+        assert_eq!(g.solve(), want.passes().map(|x| x.placed).sum::<usize>());
+        assert_eq!(g.solve(), 0);
+        assert_eq!(g.cells(), &want.cells[..]);
+    }
+
+    #[test]
+    fn test_from_sudoku() {
+        // The Sudoku's rules come along and run on the grid.
+        let mut bare = Grid::from(&presets::load_easy());
+        bare.solve();
+        assert!(!bare.is_solved());
+        let mut g = Grid::from(&presets::load_easy().with_standard_rules());
+        assert_eq!(g.to_string(), presets::load_easy().to_string());
+        g.solve();
+        assert!(g.is_solved());
+        assert!(g.is_valid());
+    }
+}
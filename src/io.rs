@@ -0,0 +1,67 @@
+// Puzzles over any reader and writer.  The command line reads "-"
+// through read_sudoku().
+
+use crate::structure::{Solver, Sudoku};
+use std::io::{self, Read, Write};
+
+/// Read a single puzzle, in any format Sudoku's FromStr takes.  Input
+/// that isn't a puzzle is an InvalidData error.
+pub fn read_sudoku<R: Read>(mut input: R) -> io::Result<Sudoku> {
+    let mut buffer = String::new();
+    input.read_to_string(&mut buffer)?;
+    buffer
+        .parse()
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "not a sudoku"))
+}
+
+/// Read a single puzzle like read_sudoku(), solve it with the
+/// standard rules and write the resulting grid.
+pub fn solve_stream<R: Read, W: Write>(input: R, mut output: W) -> io::Result<()> {
+    let mut s = read_sudoku(input)?.with_standard_rules();
+    s.solve();
+    writeln!(output, "{}", s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::presets;
+
+    #[test]
+    fn test_solve_stream() {
+        let dotted = "
+..7.....5
+5..42...1
+.4...56..
+6.51.....
+.....8...
+2......8.
+92..7..5.
+.73..6...
+..1..9..2";
+        let mut output: Vec<u8> = vec![];
+        solve_stream(dotted.as_bytes(), &mut output).unwrap();
+
+        let mut want = presets::load_hard().with_standard_rules();
+        want.solve();
+        assert!(want.is_solved());
+        assert_eq!(String::from_utf8(output).unwrap(), format!("{}\n", want));
+    }
+
+    #[test]
+    fn test_read_sudoku() {
+        let line = presets::load_hard().to_line();
+        let s = read_sudoku(line.as_bytes()).unwrap();
+        assert_eq!(s.cells, presets::load_hard().cells);
+        let err = read_sudoku("x".as_bytes()).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_solve_stream_invalid() {
+        let mut output: Vec<u8> = vec![];
+        let err = solve_stream("..7".as_bytes(), &mut output).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(output.is_empty());
+    }
+}
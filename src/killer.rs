@@ -0,0 +1,390 @@
+use crate::structure::{mask_of, parse_cell, Cell, Rule, Sudoku};
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::str::FromStr;
+
+// +------+
+// | Cage |
+// +------+
+
+/// A group of distinct cells whose values add up to sum.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cage {
+    pub cells: Vec<usize>,
+    pub sum: u8,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CageError {
+    // Line of a cage description that couldn't be parsed.
+    Parse(String),
+    // Cell index outside of the grid.
+    OutOfRange(usize),
+    // Cell listed twice, within one cage or across two cages.
+    Overlap(usize),
+    // Cell outside of every cage.
+    Gap(usize),
+    // Cage, by position, whose sum no distinct digits add up to.
+    ImpossibleSum(usize),
+    // Cage sums adding up to something other than 405, the sum of a
+    // full grid.
+    TotalMismatch(u32),
+}
+
+impl FromStr for Cage {
+    type Err = CageError;
+
+    // A cage is written as "sum: r1c1 r1c2 ...".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || CageError::Parse(s.to_string());
+        let mut parts = s.splitn(2, ':');
+        let sum = parts.next().unwrap().trim().parse().map_err(|_| err())?;
+        let cells = parts
+            .next()
+            .ok_or_else(err)?
+            .split_whitespace()
+            .map(|x| parse_cell(x).ok_or_else(err))
+            .collect::<Result<Vec<usize>, CageError>>()?;
+        if cells.is_empty() {
+            return Err(err());
+        }
+        Ok(Cage { cells, sum })
+    }
+}
+
+/// Parse one cage per line, skipping blank lines.
+pub fn parse_cages(s: &str) -> Result<Vec<Cage>, CageError> {
+    s.lines()
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+// +----------+
+// | CageRule |
+// +----------+
+
+// Candidates of a cell as a bitmask, solved cells included.
+fn candidates(c: &Cell) -> u16 {
+    if c.value() != 0 {
+        mask_of(c.value())
+    } else {
+        c.options()
+    }
+}
+
+fn digit_sum(digits: u16) -> u8 {
+    (1u8..=9).filter(|v| digits & mask_of(*v) != 0).sum()
+}
+
+// Whether each cell can take a different one of the given digits.
+fn assignable(cells: &[u16], digits: u16) -> bool {
+    match cells.split_first() {
+        None => true,
+        Some((first, rest)) => (1u8..=9)
+            .map(mask_of)
+            .filter(|m| first & digits & m != 0)
+            .any(|m| assignable(rest, digits & !m)),
+    }
+}
+
+// Cage members must be distinct and the target keeps only values
+// that take part in some combination of distinct digits adding up
+// to the cage sum, given what the other members can still hold.
+#[derive(Clone)]
+pub struct CageRule {
+    cage: Cage,
+    others: Vec<u16>,
+}
+
+impl CageRule {
+    pub fn new(cage: Cage) -> Self {
+        CageRule {
+            cage,
+            others: vec![],
+        }
+    }
+
+    pub fn cage(&self) -> &Cage {
+        &self.cage
+    }
+}
+
+impl Rule for CageRule {
+    fn name(&self) -> &str {
+        "cage"
+    }
+
+    fn begin(&mut self) {
+        self.others.clear();
+    }
+
+    fn predicate(&self, target: usize, other: usize) -> bool {
+        self.cage.cells.contains(&target) && self.cage.cells.contains(&other)
+    }
+
+    fn consider(&mut self, target: &mut Cell, other: Cell) {
+        if other.value() != 0 {
+            target.shut(other.value());
+        }
+        self.others.push(candidates(&other));
+    }
+
+    fn end(&self, target: &mut Cell) {
+        // Solved targets and targets outside of the cage are left
+        // alone.
+        if target.value() != 0 || self.others.is_empty() {
+            return;
+        }
+        let size = self.others.len() as u32 + 1;
+        let mut possible = 0u16;
+        for digits in 0u16..512 {
+            if digits.count_ones() != size || digit_sum(digits) != self.cage.sum {
+                continue;
+            }
+            for m in (1u8..=9).map(mask_of) {
+                let open = target.options() & digits & m != 0;
+                if open && possible & m == 0 && assignable(&self.others, digits & !m) {
+                    possible |= m;
+                }
+            }
+        }
+        // Avoid shutting every option of a cell, which would break
+        // its invariant.  A cage that can't be satisfied at all is
+        // left to the other rules to trip over.
+        if possible != 0 {
+            for v in 1u8..=9 {
+                if possible & mask_of(v) == 0 && target.is_open(v) {
+                    target.shut(v);
+                }
+            }
+        }
+    }
+}
+
+// +--------+
+// | Sudoku |
+// +--------+
+
+impl Sudoku {
+    /// Attach Killer Sudoku cages.  Cages may not share cells and a
+    /// single cell cage simply gives the value of that cell, marked
+    /// as a given.
+    pub fn with_cages(mut self, cages: Vec<Cage>) -> Result<Self, CageError> {
+        let mut seen = [false; 81];
+        for (k, cage) in cages.iter().enumerate() {
+            if cage.cells.len() == 1 && !(1..=9).contains(&cage.sum) {
+                return Err(CageError::ImpossibleSum(k));
+            }
+            for &index in &cage.cells {
+                if index >= 81 {
+                    return Err(CageError::OutOfRange(index));
+                }
+                if seen[index] {
+                    return Err(CageError::Overlap(index));
+                }
+                seen[index] = true;
+            }
+        }
+        for cage in cages {
+            if cage.cells.len() == 1 {
+                self.cells[cage.cells[0]].set(cage.sum);
+                self.givens[cage.cells[0]] = true;
+            } else {
+                self.rules.push(Box::new(CageRule::new(cage)));
+            }
+        }
+        Ok(self)
+    }
+}
+
+impl Sudoku {
+    /// Check cages describe a complete Killer puzzle: every cell in
+    /// exactly one cage, every sum reachable with distinct digits and
+    /// all sums adding up to 405.
+    pub fn validate_cages(cages: &[CageRule]) -> Result<(), CageError> {
+        let mut seen = [false; 81];
+        for (k, rule) in cages.iter().enumerate() {
+            let cage = rule.cage();
+            for &index in &cage.cells {
+                if index >= 81 {
+                    return Err(CageError::OutOfRange(index));
+                }
+                if seen[index] {
+                    return Err(CageError::Overlap(index));
+                }
+                seen[index] = true;
+            }
+            // n distinct digits add up to anything from 1+...+n to
+            // (10-n)+...+9, and there are only nine of them.
+            let n = cage.cells.len() as u32;
+            if !(1..=9).contains(&n) {
+                return Err(CageError::ImpossibleSum(k));
+            }
+            let sum = cage.sum as u32;
+            if sum < n * (n + 1) / 2 || sum > n * (19 - n) / 2 {
+                return Err(CageError::ImpossibleSum(k));
+            }
+        }
+        if let Some(index) = seen.iter().position(|x| !x) {
+            return Err(CageError::Gap(index));
+        }
+        let total: u32 = cages.iter().map(|x| x.cage().sum as u32).sum();
+        if total != 405 {
+            return Err(CageError::TotalMismatch(total));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::presets;
+    use crate::structure::Solver;
+
+    #[test]
+    fn test_parse_cage() {
+        let cage: Cage = "15: r1c1 r1c2 r2c1".parse().unwrap();
+        assert_eq!(cage.sum, 15);
+        assert_eq!(cage.cells, vec![0, 1, 9]);
+
+        assert!("15 r1c1".parse::<Cage>().is_err());
+        assert!("15: r0c1".parse::<Cage>().is_err());
+        assert!("15: r1c10".parse::<Cage>().is_err());
+        assert!("x: r1c1".parse::<Cage>().is_err());
+        assert!("15:".parse::<Cage>().is_err());
+    }
+
+    #[test]
+    fn test_overlapping_cages() {
+        let cages = parse_cages("3: r1c1 r1c2\n7: r1c2 r1c3").unwrap();
+        let result = Sudoku::default().with_cages(cages);
+        assert_eq!(result.err(), Some(CageError::Overlap(1)));
+
+        let cages = parse_cages("3: r1c1 r1c1").unwrap();
+        let result = Sudoku::default().with_cages(cages);
+        assert_eq!(result.err(), Some(CageError::Overlap(0)));
+    }
+
+    // One cage per row, the last one summing to 45.
+    fn row_cages(last: u8) -> Vec<CageRule> {
+        (0..9)
+            .map(|r| {
+                let sum = if r == 8 { last } else { 45 };
+                CageRule::new(Cage {
+                    cells: (r * 9..r * 9 + 9).collect(),
+                    sum,
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_validate_cages() {
+        assert_eq!(Sudoku::validate_cages(&row_cages(45)), Ok(()));
+        assert_eq!(
+            Sudoku::validate_cages(&row_cages(44)),
+            Err(CageError::ImpossibleSum(8))
+        );
+
+        // Split the last row in two with sums that add up to 40.
+        let mut cages = row_cages(45);
+        cages.pop();
+        let halves: Vec<Cage> = parse_cages(
+            "20: r9c1 r9c2 r9c3 r9c4\n\
+             20: r9c5 r9c6 r9c7 r9c8 r9c9",
+        )
+        .unwrap();
+        cages.extend(halves.into_iter().map(CageRule::new));
+        assert_eq!(
+            Sudoku::validate_cages(&cages),
+            Err(CageError::TotalMismatch(400))
+        );
+
+        cages.pop();
+        assert_eq!(Sudoku::validate_cages(&cages), Err(CageError::Gap(76)));
+
+        let cages: Vec<CageRule> = parse_cages("1: r1c1\n1: r1c2 r1c1")
+            .unwrap()
+            .into_iter()
+            .map(CageRule::new)
+            .collect();
+        assert_eq!(Sudoku::validate_cages(&cages), Err(CageError::Overlap(0)));
+        let cages = [CageRule::new("2: r1c1 r1c2".parse().unwrap())];
+        assert_eq!(
+            Sudoku::validate_cages(&cages),
+            Err(CageError::ImpossibleSum(0))
+        );
+
+        // More cells than digits, whatever the sum.
+        for n in [10, 16, 20, 81].iter() {
+            let cages = [CageRule::new(Cage {
+                cells: (0..*n).collect(),
+                sum: 45,
+            })];
+            assert_eq!(
+                Sudoku::validate_cages(&cages),
+                Err(CageError::ImpossibleSum(0)),
+                "n={}",
+                n
+            );
+        }
+        let empty = [CageRule::new(Cage {
+            cells: vec![],
+            sum: 0,
+        })];
+        assert_eq!(
+            Sudoku::validate_cages(&empty),
+            Err(CageError::ImpossibleSum(0))
+        );
+    }
+
+    #[test]
+    fn test_single_cell_cages() {
+        let cages = parse_cages("3: r1c1\n7: r2c2 r2c3").unwrap();
+        let s = Sudoku::default().with_cages(cages).unwrap();
+        assert_eq!(s.cells[0].value(), 3);
+        assert!(s.givens[0]);
+        assert_eq!(s.num_clues(), 1);
+        assert_eq!(s.rules.len(), 1);
+
+        for sum in [0, 10] {
+            let cages = vec![
+                Cage {
+                    cells: vec![0, 1],
+                    sum: 3,
+                },
+                Cage {
+                    cells: vec![2],
+                    sum,
+                },
+            ];
+            let result = Sudoku::default().with_cages(cages);
+            assert_eq!(result.err(), Some(CageError::ImpossibleSum(1)));
+        }
+    }
+
+    #[test]
+    fn test_cage_sum_3() {
+        let cages = parse_cages("3: r1c1 r1c2").unwrap();
+        let mut s = Sudoku::default().with_cages(cages).unwrap();
+        assert_eq!(s.solve(), 0);
+        assert_eq!(s.cells[0].options(), 0b0_0000_0011);
+        assert_eq!(s.cells[1].options(), 0b0_0000_0011);
+        assert_eq!(s.cells[2].num_options(), 9);
+    }
+
+    #[test]
+    fn test_killer() {
+        let mut s = presets::load_killer().with_standard_rules();
+        assert!(s.cells.iter().all(|x| x.value() == 0));
+        s.solve();
+        assert!(s.is_solved());
+    }
+}
@@ -1,3 +1,23 @@
+// Only preset file loading and the io module need std, everything
+// else gets by with alloc.  Tests always link std for the harness.
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+pub mod comparison;
+pub mod grid;
+#[cfg(feature = "std")]
+pub mod io;
+pub mod killer;
 pub mod presets;
+pub mod random;
 pub mod rules;
+pub mod samurai;
+pub mod state;
+pub mod stats;
 pub mod structure;
+pub mod symmetry;
+pub mod technique;
+pub mod thermo;
+#[cfg(feature = "wasm")]
+pub mod wasm;
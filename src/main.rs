@@ -1,27 +1,52 @@
-use std::io::{self, Read};
+use std::env;
+use std::io;
+use std::path::Path;
+use std::process;
 use sudoku::presets;
-use sudoku::rules::{ExclusionRule, SingleOptRule};
 use sudoku::structure::{Solver, Sudoku};
 
-#[allow(dead_code)]
 fn read() -> Sudoku {
-    let mut buffer = String::new();
-    let mut stdin = io::stdin(); // We get `Stdin` here.
-    stdin.read_to_string(&mut buffer).unwrap();
-    buffer.parse().unwrap()
+    match sudoku::io::read_sudoku(io::stdin().lock()) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: sudoku [--preset <{}> | --file <path> | -]",
+        presets::names().join("|")
+    );
+    process::exit(2);
+}
+
+fn load(args: &[String]) -> Sudoku {
+    match args {
+        [] => presets::load_hard(),
+        [x] if x == "-" => read(),
+        [flag, name] if flag == "--preset" => match presets::load(name) {
+            Some(s) => s,
+            None => usage(),
+        },
+        [flag, path] if flag == "--file" => match presets::load_from_file(Path::new(path)) {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        },
+        _ => usage(),
+    }
 }
 
 fn main() {
-    // let mut s = read();
-    let mut s = presets::load_hard();
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut s = load(&args).with_standard_rules();
     println!("{}\n", s);
 
-    s.rules.push(Box::new(ExclusionRule::new_row()));
-    s.rules.push(Box::new(ExclusionRule::new_col()));
-    s.rules.push(Box::new(ExclusionRule::new_square()));
-    s.rules.push(Box::new(SingleOptRule::new_row()));
-    s.rules.push(Box::new(SingleOptRule::new_col()));
-    s.rules.push(Box::new(SingleOptRule::new_square()));
     println!("Solved: {}", s.solve());
     println!("{}", s);
 }
@@ -1,88 +1,445 @@
-use crate::structure::Sudoku;
+use crate::comparison;
+use crate::killer;
+use crate::structure::{ParseSudokuError, Sudoku};
+use crate::thermo;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use core::fmt::{Display, Error, Formatter};
+#[cfg(feature = "std")]
+use std::{fs, io, path::Path};
+
+// +----------+
+// | Registry |
+// +----------+
+
+const NAMES: &[&str] = &[
+    "easy",
+    "hard",
+    "hard2",
+    "expert",
+    "x",
+    "hyper",
+    "killer",
+    "anti_knight",
+    "non_consecutive",
+    "comparison",
+    "thermo",
+];
+
+const EASY: &str = "
+    3 4 0 0 0 0 0 7 0
+    8 0 0 4 0 7 2 5 0
+    7 0 6 8 0 0 3 0 9
+    0 1 3 0 0 6 4 0 0
+    0 0 7 0 0 4 0 1 0
+    0 0 4 0 0 0 6 0 3
+    0 7 9 6 5 0 1 0 2
+    0 0 0 7 0 0 5 9 8
+    0 3 0 2 9 1 7 0 0";
+
+const HARD: &str = "
+    0 0 7 0 0 0 0 0 5
+    5 0 0 4 2 0 0 0 1
+    0 4 0 0 0 5 6 0 0
+    6 0 5 1 0 0 0 0 0
+    0 0 0 0 0 8 0 0 0
+    2 0 0 0 0 0 0 8 0
+    9 2 0 0 7 0 0 5 0
+    0 7 3 0 0 6 0 0 0
+    0 0 1 0 0 9 0 0 2";
+
+const HARD2: &str = "
+     1 0 7 | 6 4 9 | 3 0 2
+     2 0 0 | 3 7 5 | 0 1 6
+     3 0 6 | 8 2 1 | 4 0 0
+    -------+-------+-------
+     0 1 0 | 0 9 6 | 7 3 8
+     7 6 3 | 0 1 8 | 0 4 9
+     0 0 0 | 0 3 7 | 6 0 1
+    -------+-------+-------
+     6 7 0 | 1 5 3 | 0 0 4
+     8 3 1 | 9 6 0 | 0 0 0
+     0 0 0 | 7 8 0 | 1 6 3";
+
+const EXPERT: &str = "
+    0 4 0 9 0 0 0 0 7
+    1 9 0 6 0 0 0 0 4
+    5 0 0 0 0 0 0 1 0
+    0 8 0 0 3 0 0 7 0
+    2 0 0 0 0 4 5 0 8
+    0 0 0 5 0 0 0 0 0
+    0 0 0 0 0 0 0 2 0
+    0 0 0 0 0 0 3 4 0
+    0 7 0 0 0 6 0 0 1";
+
+// X-Sudoku: needs diagonal rules.
+const X: &str = "
+    0 0 3 0 0 0 4 0 0
+    0 0 0 8 6 7 0 5 0
+    0 0 0 3 0 0 0 0 0
+    0 0 2 0 0 0 0 0 0
+    0 0 0 5 0 0 0 0 0
+    1 3 0 0 0 4 2 0 5
+    0 5 0 7 0 0 0 0 0
+    0 0 9 0 0 8 0 0 0
+    0 6 0 9 5 3 0 0 0";
+
+// Hyper Sudoku (Windoku): needs window rules.
+const HYPER: &str = "
+    4 0 0 8 7 0 0 2 0
+    0 0 0 2 0 0 0 8 6
+    0 0 0 0 0 0 9 0 0
+    0 0 0 0 3 0 0 5 0
+    0 0 0 0 0 0 0 7 0
+    8 0 0 7 0 0 2 0 1
+    0 0 8 0 0 5 0 0 0
+    3 6 0 0 0 0 0 0 0
+    0 0 4 0 0 0 0 9 0";
+
+// Anti-knight: needs anti-knight rules.
+const ANTI_KNIGHT: &str = "
+    0 1 0 0 0 0 6 0 0
+    0 0 0 0 7 0 8 0 5
+    0 0 0 0 0 0 0 0 0
+    0 0 0 8 0 4 0 0 0
+    0 0 0 0 0 0 0 0 8
+    5 0 0 0 0 3 0 1 7
+    0 0 0 5 0 0 0 0 3
+    6 0 0 0 0 0 0 0 0
+    0 0 0 3 2 0 0 0 0";
+
+// Non-consecutive: needs the non-consecutive rule.
+const NON_CONSECUTIVE: &str = "
+    3 0 8 0 0 7 0 0 0
+    0 0 0 0 0 0 0 7 0
+    0 0 1 0 0 9 0 0 0
+    0 0 0 0 0 0 0 0 0
+    0 0 0 0 5 0 0 0 0
+    0 0 0 0 0 0 0 6 0
+    0 0 0 4 0 1 0 0 0
+    0 0 0 0 0 5 7 0 0
+    0 0 0 0 0 0 2 0 0";
+
+// Killer Sudoku: no givens, only cages.
+const KILLER: &str = "
+    35: r1c1 r2c1 r2c2 r2c3 r3c1
+    7: r1c2 r1c3
+    12: r1c4 r2c4
+    3: r1c5 r1c6
+    34: r1c7 r1c8 r1c9 r2c6 r2c7
+    35: r2c5 r3c4 r3c5 r3c6 r4c6
+    7: r2c8 r2c9 r3c8
+    3: r3c2 r3c3
+    9: r3c7 r4c7 r4c8
+    9: r3c9 r4c9
+    36: r4c1 r4c2 r5c1 r5c2 r6c1 r6c2
+    11: r4c3 r4c4 r5c3 r6c3
+    10: r4c5 r5c5
+    9: r5c4 r6c4
+    23: r5c6 r5c7 r5c8
+    11: r5c9 r6c9
+    9: r6c5 r6c6
+    7: r6c7 r6c8
+    4: r7c1 r8c1
+    6: r7c2 r8c2
+    34: r7c3 r8c3 r8c4 r8c5 r9c5
+    10: r7c4 r7c5
+    11: r7c6 r8c6 r9c6 r9c7
+    15: r7c7 r7c8
+    7: r7c9 r8c9
+    10: r8c7 r8c8
+    13: r9c1 r9c2
+    13: r9c3 r9c4
+    12: r9c8 r9c9";
+
+// Comparison Sudoku: no givens, only "smaller larger" cell pairs.
+const COMPARISON: &str = "
+    r1c2 r1c1  r1c1 r2c1  r1c3 r1c2  r2c2 r1c2  r1c3 r2c3  r1c5 r1c4
+    r2c4 r1c4  r1c5 r1c6  r2c5 r1c5  r2c6 r1c6  r1c7 r1c8  r1c7 r2c7
+    r1c9 r1c8  r1c8 r2c8  r1c9 r2c9  r2c2 r2c1  r3c1 r2c1  r2c2 r2c3
+    r3c2 r2c2  r3c3 r2c3  r2c5 r2c4  r2c4 r3c4  r2c5 r2c6  r2c5 r3c5
+    r3c6 r2c6  r2c7 r2c8  r2c7 r3c7  r2c9 r2c8  r2c8 r3c8  r2c9 r3c9
+    r3c1 r3c2  r3c2 r3c3  r3c5 r3c4  r3c5 r3c6  r3c8 r3c7  r3c8 r3c9
+    r4c1 r4c2  r4c1 r5c1  r4c3 r4c2  r4c2 r5c2  r5c3 r4c3  r4c4 r4c5
+    r4c4 r5c4  r4c6 r4c5  r5c5 r4c5  r5c6 r4c6  r4c8 r4c7  r4c7 r5c7
+    r4c8 r4c9  r4c8 r5c8  r4c9 r5c9  r5c1 r5c2  r6c1 r5c1  r5c3 r5c2
+    r6c2 r5c2  r6c3 r5c3  r5c5 r5c4  r5c4 r6c4  r5c6 r5c5  r5c5 r6c5
+    r5c6 r6c6  r5c8 r5c7  r5c7 r6c7  r5c8 r5c9  r6c8 r5c8  r6c9 r5c9
+    r6c2 r6c1  r6c2 r6c3  r6c5 r6c4  r6c6 r6c5  r6c8 r6c7  r6c9 r6c8
+    r7c1 r7c2  r8c1 r7c1  r7c3 r7c2  r7c2 r8c2  r7c3 r8c3  r7c4 r7c5
+    r8c4 r7c4  r7c5 r7c6  r8c5 r7c5  r8c6 r7c6  r7c8 r7c7  r7c7 r8c7
+    r7c8 r7c9  r7c8 r8c8  r8c9 r7c9  r8c1 r8c2  r8c1 r9c1  r8c2 r8c3
+    r9c2 r8c2  r9c3 r8c3  r8c4 r8c5  r8c4 r9c4  r8c6 r8c5  r9c5 r8c5
+    r9c6 r8c6  r8c7 r8c8  r9c7 r8c7  r8c9 r8c8  r9c8 r8c8  r9c9 r8c9
+    r9c2 r9c1  r9c3 r9c2  r9c5 r9c4  r9c6 r9c5  r9c7 r9c8  r9c9 r9c8";
+
+// Thermo Sudoku: givens and thermometers, bulb first.
+const THERMO: &str = "
+    0 0 0 0 0 0 0 2 0
+    0 0 0 3 0 0 0 0 0
+    7 0 0 0 0 0 0 0 0
+    0 0 0 6 5 0 0 0 0
+    0 0 0 0 0 0 0 0 8
+    0 8 0 1 0 0 0 0 0
+    0 0 0 0 0 0 0 0 7
+    0 0 0 0 0 0 0 0 0
+    1 0 2 0 0 0 0 0 0";
+
+const THERMO_PATHS: &str = "
+    r1c1 r2c2 r1c3 r1c2 r2c1
+    r6c4 r5c5 r4c4 r4c3
+    r8c1 r7c2 r6c1 r6c2
+    r8c7 r7c7 r6c6 r5c6
+    r2c4 r3c5 r4c6 r3c6
+    r7c3 r8c3 r9c4 r8c4
+    r3c8 r3c9 r4c9 r5c9";
+
+fn grid(name: &str) -> Option<&'static str> {
+    match name {
+        "easy" => Some(EASY),
+        "hard" => Some(HARD),
+        "hard2" => Some(HARD2),
+        "expert" => Some(EXPERT),
+        "x" => Some(X),
+        "hyper" => Some(HYPER),
+        "anti_knight" => Some(ANTI_KNIGHT),
+        "non_consecutive" => Some(NON_CONSECUTIVE),
+        _ => None,
+    }
+}
+
+/// Names of all built-in presets.
+pub fn names() -> &'static [&'static str] {
+    NAMES
+}
+
+/// Load a built-in preset by name or None if there's no such preset.
+pub fn load(name: &str) -> Option<Sudoku> {
+    match name {
+        "killer" => return Some(load_killer()),
+        "comparison" => return Some(load_comparison()),
+        "thermo" => return Some(load_thermo()),
+        _ => (),
+    }
+    // Built-in grids are known to be well-formed.
+    grid(name).map(|x| x.parse().unwrap())
+}
 
 pub fn load_easy() -> Sudoku {
-    let mut s = Sudoku::default();
-    s.cells[0].set(3);
-    s.cells[1].set(4);
-    s.cells[7].set(7);
-    s.cells[9].set(8);
-    s.cells[12].set(4);
-    s.cells[14].set(7);
-    s.cells[15].set(2);
-    s.cells[16].set(5);
-    s.cells[18].set(7);
-    s.cells[20].set(6);
-    s.cells[21].set(8);
-    s.cells[24].set(3);
-    s.cells[26].set(9);
-    s.cells[28].set(1);
-    s.cells[29].set(3);
-    s.cells[32].set(6);
-    s.cells[33].set(4);
-    s.cells[38].set(7);
-    s.cells[41].set(4);
-    s.cells[43].set(1);
-    s.cells[47].set(4);
-    s.cells[51].set(6);
-    s.cells[53].set(3);
-    s.cells[55].set(7);
-    s.cells[56].set(9);
-    s.cells[57].set(6);
-    s.cells[58].set(5);
-    s.cells[60].set(1);
-    s.cells[62].set(2);
-    s.cells[66].set(7);
-    s.cells[69].set(5);
-    s.cells[70].set(9);
-    s.cells[71].set(8);
-    s.cells[73].set(3);
-    s.cells[75].set(2);
-    s.cells[76].set(9);
-    s.cells[77].set(1);
-    s.cells[78].set(7);
-    s
+    load("easy").unwrap()
 }
 
 pub fn load_hard() -> Sudoku {
-    "0 0 7 0 0 0 0 0 5
-     5 0 0 4 2 0 0 0 1
-     0 4 0 0 0 5 6 0 0
-     6 0 5 1 0 0 0 0 0
-     0 0 0 0 0 8 0 0 0
-     2 0 0 0 0 0 0 8 0
-     9 2 0 0 7 0 0 5 0
-     0 7 3 0 0 6 0 0 0
-     0 0 1 0 0 9 0 0 2"
-        .parse()
-        .unwrap()
+    load("hard").unwrap()
 }
 
 pub fn load_hard2() -> Sudoku {
-    " 1 0 7 | 6 4 9 | 3 0 2
-      2 0 0 | 3 7 5 | 0 1 6
-      3 0 6 | 8 2 1 | 4 0 0
-     -------+-------+-------
-      0 1 0 | 0 9 6 | 7 3 8
-      7 6 3 | 0 1 8 | 0 4 9
-      0 0 0 | 0 3 7 | 6 0 1
-     -------+-------+-------
-      6 7 0 | 1 5 3 | 0 0 4
-      8 3 1 | 9 6 0 | 0 0 0
-      0 0 0 | 7 8 0 | 1 6 3"
-        .parse()
-        .unwrap()
+    load("hard2").unwrap()
 }
 
 pub fn load_expert() -> Sudoku {
-    "0 4 0 9 0 0 0 0 7
-     1 9 0 6 0 0 0 0 4
-     5 0 0 0 0 0 0 1 0
-     0 8 0 0 3 0 0 7 0
-     2 0 0 0 0 4 5 0 8
-     0 0 0 5 0 0 0 0 0
-     0 0 0 0 0 0 0 2 0
-     0 0 0 0 0 0 3 4 0
-     0 7 0 0 0 6 0 0 1"
-        .parse()
-        .unwrap()
+    load("expert").unwrap()
+}
+
+pub fn load_x() -> Sudoku {
+    load("x").unwrap()
+}
+
+pub fn load_hyper() -> Sudoku {
+    load("hyper").unwrap()
+}
+
+pub fn load_anti_knight() -> Sudoku {
+    load("anti_knight").unwrap()
+}
+
+pub fn load_non_consecutive() -> Sudoku {
+    load("non_consecutive").unwrap()
+}
+
+/// Empty grid with the Killer Sudoku cage rules attached.
+pub fn load_killer() -> Sudoku {
+    let cages = killer::parse_cages(KILLER).unwrap();
+    Sudoku::default().with_cages(cages).unwrap()
+}
+
+/// Thermo Sudoku with its thermometers attached.
+pub fn load_thermo() -> Sudoku {
+    let mut s: Sudoku = THERMO.parse().unwrap();
+    for cells in thermo::parse_thermometers(THERMO_PATHS).unwrap() {
+        s.add_thermometer(cells).unwrap();
+    }
+    s
+}
+
+/// Empty grid with the comparison rules attached.
+pub fn load_comparison() -> Sudoku {
+    let mut s = Sudoku::default();
+    for (smaller, larger) in comparison::parse_inequalities(COMPARISON).unwrap() {
+        s.add_inequality(smaller, larger).unwrap();
+    }
+    s
+}
+
+// +-------+
+// | Files |
+// +-------+
+
+// Reading files takes std, the rest of the presets are built in.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub enum PresetError {
+    Io(io::Error),
+    Parse(ParseSudokuError),
+}
+
+#[cfg(feature = "std")]
+impl Display for PresetError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            PresetError::Io(e) => write!(f, "cannot read preset: {}", e),
+            PresetError::Parse(_) => write!(f, "cannot parse preset"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PresetError {}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for PresetError {
+    fn from(e: io::Error) -> Self {
+        PresetError::Io(e)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<ParseSudokuError> for PresetError {
+    fn from(e: ParseSudokuError) -> Self {
+        PresetError::Parse(e)
+    }
+}
+
+/// Read a puzzle from a text file in any format Sudoku::from_str
+/// understands.
+#[cfg(feature = "std")]
+pub fn load_from_file(path: &Path) -> Result<Sudoku, PresetError> {
+    let text = fs::read_to_string(path)?;
+    Ok(text.parse()?)
+}
+
+// +------------+
+// | Collection |
+// +------------+
+
+const COLLECTION: &str = include_str!("collection.txt");
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+}
+
+pub struct CollectionEntry {
+    pub name: &'static str,
+    pub difficulty: Difficulty,
+    pub puzzle: Sudoku,
+    pub solution: Sudoku,
+}
+
+fn parse_entry(line: &'static str) -> Result<CollectionEntry, ParseSudokuError> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    if fields.len() != 4 {
+        return Err(ParseSudokuError {});
+    }
+    let difficulty = match fields[1] {
+        "easy" => Difficulty::Easy,
+        "medium" => Difficulty::Medium,
+        "hard" => Difficulty::Hard,
+        _ => return Err(ParseSudokuError {}),
+    };
+    Ok(CollectionEntry {
+        name: fields[0],
+        difficulty,
+        puzzle: fields[2].parse()?,
+        solution: fields[3].parse()?,
+    })
+}
+
+/// Graded puzzles with their known unique solutions.  The embedded
+/// file is only parsed when this is called.
+pub fn collection() -> Vec<CollectionEntry> {
+    COLLECTION
+        .lines()
+        .map(str::trim)
+        .filter(|x| !x.is_empty() && !x.starts_with('#'))
+        .map(|x| match parse_entry(x) {
+            Ok(entry) => entry,
+            Err(_) => panic!("malformed collection entry: {}", x),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::{ExclusionRule, SingleOptRule};
+    use crate::structure::Solver;
+
+    #[test]
+    fn test_names_load() {
+        for name in names() {
+            assert!(load(name).is_some(), "name={}", name);
+        }
+        assert!(load("nonexistent").is_none());
+    }
+
+    #[test]
+    fn test_load_easy() {
+        assert_eq!(load("easy").unwrap().cells, load_easy().cells);
+        assert_eq!(load_easy().cells[0].value(), 3);
+        assert_eq!(load_easy().cells[78].value(), 7);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_load_from_file_missing() {
+        let result = load_from_file(Path::new("/nonexistent/sudoku.txt"));
+        match result {
+            Err(PresetError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::NotFound),
+            _ => panic!("expected an IO error"),
+        }
+    }
+
+    #[test]
+    fn test_collection() {
+        let entries = collection();
+        assert_eq!(entries.len(), 50);
+        for entry in entries {
+            let mut s = entry.puzzle;
+            s.rules.push(Box::new(ExclusionRule::new_row()));
+            s.rules.push(Box::new(ExclusionRule::new_col()));
+            s.rules.push(Box::new(ExclusionRule::new_square()));
+            if entry.difficulty != Difficulty::Easy {
+                s.rules.push(Box::new(SingleOptRule::new_row()));
+                s.rules.push(Box::new(SingleOptRule::new_col()));
+                s.rules.push(Box::new(SingleOptRule::new_square()));
+            }
+            s.solve();
+            assert!(entry.solution.is_solved(), "name={}", entry.name);
+            if entry.difficulty == Difficulty::Hard {
+                // Rules alone get stuck, but whatever they place must
+                // agree with the solution.
+                assert!(!s.is_solved(), "name={}", entry.name);
+                for (have, want) in s.cells.iter().zip(entry.solution.cells.iter()) {
+                    assert!(have.value() == 0 || have == want, "name={}", entry.name);
+                }
+                let solved = s.solved().unwrap();
+                assert_eq!(solved.cells, entry.solution.cells, "name={}", entry.name);
+            } else {
+                assert_eq!(s.cells, entry.solution.cells, "name={}", entry.name);
+            }
+        }
+    }
 }
@@ -0,0 +1,233 @@
+use crate::structure::Sudoku;
+
+// +-----+
+// | Rng |
+// +-----+
+
+/// Small deterministic generator (SplitMix64), plenty for shuffling
+/// puzzles around.  The same seed always gives the same sequence.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut x = self.state;
+        x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        x ^ (x >> 31)
+    }
+
+    /// Number in 0..n, n must not be 0.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// Fisher-Yates shuffle.
+    pub fn shuffle<T>(&mut self, xs: &mut [T]) {
+        for i in (1..xs.len()).rev() {
+            xs.swap(i, self.below(i + 1));
+        }
+    }
+}
+
+// +--------+
+// | Sudoku |
+// +--------+
+
+impl Sudoku {
+    /// Random solved grid, the same one for the same seed.
+    pub fn random_solved(seed: u64) -> Sudoku {
+        // The three squares on the main diagonal don't see each
+        // other, so any values will do there.  Search fills in the
+        // rest.
+        let mut rng = Rng::new(seed);
+        let mut s = Sudoku::default();
+        for square in 0..3 {
+            let mut values: [u8; 9] = [1, 2, 3, 4, 5, 6, 7, 8, 9];
+            rng.shuffle(&mut values);
+            for (k, v) in values.iter().enumerate() {
+                let row = square * 3 + k / 3;
+                let col = square * 3 + k % 3;
+                s.cells[row * 9 + col].set(*v);
+            }
+        }
+        // Any three consistent squares on the diagonal can be
+        // completed, so there's always a solution.  Searching with
+        // the standard rules keeps the search tree small.
+        let mut x = s.with_standard_rules().solved().unwrap();
+        x.rules.clear();
+        x
+    }
+
+    /// Puzzle keeping the given number of cells of random_solved(),
+    /// which is bound to have a solution but not necessarily a unique
+    /// one.
+    pub fn random_puzzle(seed: u64, clues: usize) -> Sudoku {
+        Sudoku::random_solved(seed).masked(seed, clues)
+    }
+
+    /// Copy keeping the values of the given number of randomly picked
+    /// cells as givens, all other cells emptied.
+    pub fn masked(&self, seed: u64, clues: usize) -> Sudoku {
+        let mut order: [usize; 81] = [0; 81];
+        for (i, x) in order.iter_mut().enumerate() {
+            *x = i;
+        }
+        Rng::new(seed).shuffle(&mut order);
+
+        let mut s = Sudoku::default();
+        for &i in order.iter().take(clues) {
+            s.cells[i].set(self.cells[i].value());
+        }
+        s.mark_givens();
+        s
+    }
+}
+
+// +----------+
+// | Strategy |
+// +----------+
+
+/// proptest strategies built on random_solved(), so failing cases
+/// come with a seed that reproduces them.
+#[cfg(feature = "proptest")]
+pub mod strategy {
+    use crate::structure::Sudoku;
+    use proptest::prelude::*;
+
+    /// Valid solved grids.
+    pub fn solved() -> impl Strategy<Value = Sudoku> {
+        any::<u64>().prop_map(Sudoku::random_solved)
+    }
+
+    /// Puzzles with 17 to 81 givens masked out of a solved grid,
+    /// paired with that grid.  Not every puzzle has a unique
+    /// solution, the grid is one of them.
+    pub fn puzzle() -> impl Strategy<Value = (Sudoku, Sudoku)> {
+        (any::<u64>(), 17..=81usize).prop_map(|(seed, clues)| {
+            let solution = Sudoku::random_solved(seed);
+            (solution.masked(seed, clues), solution)
+        })
+    }
+
+    impl Arbitrary for Sudoku {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Sudoku>;
+
+        fn arbitrary_with(_: ()) -> Self::Strategy {
+            solved().boxed()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{format, string::String};
+
+    // Property checks run over this many seeds.
+    const CASES: u64 = 200;
+
+    #[test]
+    fn test_rng() {
+        let mut a = Rng::new(7);
+        let mut b = Rng::new(7);
+        assert_eq!(a.next_u64(), b.next_u64());
+        assert_ne!(a.next_u64(), Rng::new(8).next_u64());
+
+        let mut xs = [0, 1, 2, 3, 4, 5, 6, 7];
+        a.shuffle(&mut xs);
+        xs.sort_unstable();
+        assert_eq!(xs, [0, 1, 2, 3, 4, 5, 6, 7]);
+        assert!((0..100).all(|_| a.below(3) < 3));
+    }
+
+    #[test]
+    fn test_random_solved() {
+        for seed in 0..20 {
+            let s = Sudoku::random_solved(seed);
+            assert!(s.is_solved() && s.is_valid(), "seed={}", seed);
+            assert!(s.same_values(&Sudoku::random_solved(seed)));
+        }
+        assert!(!Sudoku::random_solved(1).same_values(&Sudoku::random_solved(2)));
+        assert!(Sudoku::random_solved(1).rules.is_empty());
+    }
+
+    #[test]
+    fn test_random_puzzle() {
+        let s = Sudoku::random_puzzle(3, 30);
+        assert_eq!(s.num_clues(), 30);
+        assert!(s.givens.iter().filter(|x| **x).count() == 30);
+        let solution = Sudoku::random_solved(3);
+        for (have, want) in s.cells.iter().zip(solution.cells.iter()) {
+            assert!(have.value() == 0 || have == want);
+        }
+    }
+
+    // Rule deductions hold in every solution, so the value of the
+    // solution the puzzle was cut from stays open in every unsolved
+    // cell, whatever gets placed matches it, and the board stays
+    // valid pass after pass.
+    fn check_rules_agree(puzzle: Sudoku, solution: &Sudoku) -> Result<(), String> {
+        let mut s = puzzle.with_standard_rules();
+        let mut stalled = 0;
+        while !s.is_solved() && stalled < 3 {
+            if s.solve_step() == 0 {
+                stalled += 1;
+            }
+            if !s.is_valid() {
+                return Err(format!("invalid after a pass: {}", s.to_line()));
+            }
+            for (i, (have, want)) in s.cells.iter().zip(solution.cells.iter()).enumerate() {
+                if have.value() == 0 && !have.is_open(want.value()) {
+                    return Err(format!("{} eliminated at {}", want.value(), i));
+                }
+            }
+        }
+        for (have, want) in s.cells.iter().zip(solution.cells.iter()) {
+            if have.value() != 0 && have != want {
+                return Err(format!(
+                    "{} placed instead of {}",
+                    have.value(),
+                    want.value()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_property_rules_agree_with_solution() {
+        for seed in 0..CASES {
+            let solution = Sudoku::random_solved(seed);
+            let clues = 25 + seed as usize % 30;
+            let puzzle = solution.masked(seed, clues);
+            if let Err(e) = check_rules_agree(puzzle, &solution) {
+                panic!("seed={}: {}", seed, e);
+            }
+        }
+    }
+
+    #[cfg(feature = "proptest")]
+    proptest::proptest! {
+        #![proptest_config(proptest::prelude::ProptestConfig::with_cases(CASES as u32))]
+
+        #[test]
+        fn prop_rules_agree_with_solution((puzzle, solution) in strategy::puzzle()) {
+            let result = check_rules_agree(puzzle, &solution);
+            proptest::prop_assert!(result.is_ok(), "{:?}", result);
+        }
+
+        #[test]
+        fn prop_solved_is_valid(s in proptest::prelude::any::<Sudoku>()) {
+            proptest::prop_assert!(s.is_solved() && s.is_valid());
+        }
+    }
+}
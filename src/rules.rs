@@ -1,22 +1,109 @@
+use crate::grid::Dimensions;
 #[allow(unused_imports)]
-use crate::structure::{Cell, Rule};
+use crate::structure::{Cell, Change, ChangeKind, Rule, Sudoku, Unit};
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
 
 // +------------+
 // | Predicates |
 // +------------+
 
 fn same_row(a: usize, b: usize) -> bool {
-    a / 9 == b / 9
+    Unit::Row.contains(a, b)
 }
 
 fn same_col(a: usize, b: usize) -> bool {
-    a % 9 == b % 9
+    Unit::Col.contains(a, b)
 }
 
 fn same_square(a: usize, b: usize) -> bool {
-    let row = |i| i / 27;
-    let col = |i| i % 9 / 3;
-    row(a) == row(b) && col(a) == col(b)
+    Unit::Square.contains(a, b)
+}
+
+// Rules keep plain function pointers for predicates.
+fn unit_predicate(unit: Unit) -> fn(usize, usize) -> bool {
+    match unit {
+        Unit::Row => same_row,
+        Unit::Col => same_col,
+        Unit::Square => same_square,
+    }
+}
+
+// Cells a rule relates: a 9x9 predicate, or a unit of a grid of the
+// given size.
+#[derive(Clone, Copy)]
+enum Scope {
+    Fn(fn(usize, usize) -> bool),
+    Unit(Dimensions, Unit),
+}
+
+impl Scope {
+    fn contains(&self, a: usize, b: usize) -> bool {
+        match self {
+            Scope::Fn(f) => f(a, b),
+            Scope::Unit(dims, unit) => dims.contains(*unit, a, b),
+        }
+    }
+}
+
+// Only cells on a diagonal belong to it, every other cell is
+// unrelated to all cells.
+fn same_diagonal_main(a: usize, b: usize) -> bool {
+    let on = |i| i / 9 == i % 9;
+    on(a) && on(b)
+}
+
+fn same_diagonal_anti(a: usize, b: usize) -> bool {
+    let on = |i| i / 9 + i % 9 == 8;
+    on(a) && on(b)
+}
+
+/// Index (0-3) of the Hyper Sudoku window containing the cell, if
+/// any.  Windows span rows and columns 1-3 and 5-7.
+pub fn window_of(index: usize) -> Option<usize> {
+    let band = |x: usize| match x {
+        1..=3 => Some(0),
+        5..=7 => Some(1),
+        _ => None,
+    };
+    match (band(index / 9), band(index % 9)) {
+        (Some(row), Some(col)) => Some(row * 2 + col),
+        _ => None,
+    }
+}
+
+fn same_window(a: usize, b: usize) -> bool {
+    match window_of(a) {
+        Some(w) => window_of(b) == Some(w),
+        None => false,
+    }
+}
+
+// Row and column distance of two cells.  Computed from the row and
+// column of each cell rather than the index difference, which would
+// wrap around board edges.
+fn distance(a: usize, b: usize) -> (usize, usize) {
+    let dr = (a / 9).max(b / 9) - (a / 9).min(b / 9);
+    let dc = (a % 9).max(b % 9) - (a % 9).min(b % 9);
+    (dr, dc)
+}
+
+fn same_knight_move(a: usize, b: usize) -> bool {
+    matches!(distance(a, b), (1, 2) | (2, 1))
+}
+
+fn same_king_move(a: usize, b: usize) -> bool {
+    let (dr, dc) = distance(a, b);
+    a != b && dr <= 1 && dc <= 1
+}
+
+// Orthogonally adjacent cells.
+fn adjacent(a: usize, b: usize) -> bool {
+    matches!(distance(a, b), (0, 1) | (1, 0))
 }
 
 // +---------------+
@@ -25,39 +112,109 @@ fn same_square(a: usize, b: usize) -> bool {
 
 // Target cell cannot have the value of other cells form the same
 // subgroup.
+#[derive(Clone)]
 pub struct ExclusionRule {
-    predicate_fn: fn(usize, usize) -> bool,
+    name: &'static str,
+    scope: Scope,
+    target: usize,
+    changes: Vec<Change>,
 }
 
 impl Rule for ExclusionRule {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn begin_pass(&mut self) {
+        self.changes.clear();
+    }
+
+    fn begin_at(&mut self, target: usize) {
+        self.target = target;
+    }
+
     fn predicate(&self, target: usize, other: usize) -> bool {
-        (self.predicate_fn)(target, other)
+        self.scope.contains(target, other)
     }
 
     fn consider(&mut self, target: &mut Cell, other: Cell) {
-        if other.value() != 0 {
-            target.shut(other.value());
+        let v = other.value();
+        if v != 0 && target.is_open(v) {
+            target.shut(v);
+            self.changes.push(Change {
+                index: self.target,
+                kind: ChangeKind::Eliminated(v),
+            });
         }
     }
+
+    fn last_changes(&self) -> &[Change] {
+        &self.changes
+    }
 }
 
 impl ExclusionRule {
-    pub fn new_row() -> Self {
+    fn new(name: &'static str, predicate_fn: fn(usize, usize) -> bool) -> Self {
+        Self::with_scope(name, Scope::Fn(predicate_fn))
+    }
+
+    fn with_scope(name: &'static str, scope: Scope) -> Self {
         ExclusionRule {
-            predicate_fn: same_row,
+            name,
+            scope,
+            target: 0,
+            changes: vec![],
         }
     }
 
-    pub fn new_col() -> Self {
-        ExclusionRule {
-            predicate_fn: same_col,
+    fn unit_name(unit: Unit) -> &'static str {
+        match unit {
+            Unit::Row => "exclusion_row",
+            Unit::Col => "exclusion_col",
+            Unit::Square => "exclusion_square",
         }
     }
 
+    /// Rule for the rows, columns or squares.
+    pub fn for_unit(unit: Unit) -> Self {
+        Self::new(Self::unit_name(unit), unit_predicate(unit))
+    }
+
+    /// Rule for the rows, columns or boxes of a Grid.
+    pub fn for_grid(dims: Dimensions, unit: Unit) -> Self {
+        Self::with_scope(Self::unit_name(unit), Scope::Unit(dims, unit))
+    }
+
+    pub fn new_row() -> Self {
+        Self::for_unit(Unit::Row)
+    }
+
+    pub fn new_col() -> Self {
+        Self::for_unit(Unit::Col)
+    }
+
     pub fn new_square() -> Self {
-        ExclusionRule {
-            predicate_fn: same_square,
-        }
+        Self::for_unit(Unit::Square)
+    }
+
+    pub fn new_diag_main() -> Self {
+        Self::new("exclusion_diag_main", same_diagonal_main)
+    }
+
+    pub fn new_diag_anti() -> Self {
+        Self::new("exclusion_diag_anti", same_diagonal_anti)
+    }
+
+    pub fn new_window() -> Self {
+        Self::new("exclusion_window", same_window)
+    }
+
+    pub fn new_anti_knight() -> Self {
+        Self::new("exclusion_anti_knight", same_knight_move)
+    }
+
+    pub fn new_anti_king() -> Self {
+        Self::new("exclusion_anti_king", same_king_move)
     }
 }
 
@@ -67,60 +224,589 @@ impl ExclusionRule {
 
 // Single option rules check if the target cell is the only one in the
 // subgroup (row / col / square) that may have a particular value.
+#[derive(Clone)]
 pub struct SingleOptRule {
-    options: [u8; 10],
-    predicate_fn: fn(usize, usize) -> bool,
+    options: [u8; 17],
+    // Number of other cells in the target's subgroup.  Zero means
+    // the target isn't part of any subgroup (e.g. it's not on a
+    // diagonal).
+    members: u8,
+    // Last other cell seen with each value open.  Not used yet, but
+    // locked candidates will need to know where the options are.
+    last: [Option<usize>; 17],
+    name: &'static str,
+    scope: Scope,
+    target: usize,
+    changes: Vec<Change>,
 }
 
 impl SingleOptRule {
-    pub fn new_row() -> Self {
+    /// Last cell other than the current target found with value open,
+    /// within the target's subgroup.
+    pub fn last_open(&self, value: u8) -> Option<usize> {
+        self.last[value as usize]
+    }
+
+    fn new(name: &'static str, predicate_fn: fn(usize, usize) -> bool) -> Self {
+        Self::with_scope(name, Scope::Fn(predicate_fn))
+    }
+
+    fn with_scope(name: &'static str, scope: Scope) -> Self {
         SingleOptRule {
-            options: [0; 10],
-            predicate_fn: same_row,
+            options: [0; 17],
+            members: 0,
+            last: [None; 17],
+            name,
+            scope,
+            target: 0,
+            changes: vec![],
         }
     }
 
-    pub fn new_col() -> Self {
-        SingleOptRule {
-            options: [0; 10],
-            predicate_fn: same_col,
+    fn unit_name(unit: Unit) -> &'static str {
+        match unit {
+            Unit::Row => "single_opt_row",
+            Unit::Col => "single_opt_col",
+            Unit::Square => "single_opt_square",
         }
     }
 
+    /// Rule for the rows, columns or squares.
+    pub fn for_unit(unit: Unit) -> Self {
+        Self::new(Self::unit_name(unit), unit_predicate(unit))
+    }
+
+    /// Rule for the rows, columns or boxes of a Grid.
+    pub fn for_grid(dims: Dimensions, unit: Unit) -> Self {
+        Self::with_scope(Self::unit_name(unit), Scope::Unit(dims, unit))
+    }
+
+    pub fn new_row() -> Self {
+        Self::for_unit(Unit::Row)
+    }
+
+    pub fn new_col() -> Self {
+        Self::for_unit(Unit::Col)
+    }
+
     pub fn new_square() -> Self {
-        SingleOptRule {
-            options: [0; 10],
-            predicate_fn: same_square,
-        }
+        Self::for_unit(Unit::Square)
+    }
+
+    pub fn new_diag_main() -> Self {
+        Self::new("single_opt_diag_main", same_diagonal_main)
+    }
+
+    pub fn new_diag_anti() -> Self {
+        Self::new("single_opt_diag_anti", same_diagonal_anti)
+    }
+
+    pub fn new_window() -> Self {
+        Self::new("single_opt_window", same_window)
     }
 }
 
 impl Rule for SingleOptRule {
-    fn begin(&mut self) {
-        for i in 0..10 {
-            self.options[i] = 0;
-        }
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn begin_pass(&mut self) {
+        self.changes.clear();
+    }
+
+    fn begin_at(&mut self, target: usize) {
+        self.options = [0; 17];
+        self.members = 0;
+        self.last = [None; 17];
+        self.target = target;
     }
 
     fn predicate(&self, target: usize, other: usize) -> bool {
-        (self.predicate_fn)(target, other)
+        self.scope.contains(target, other)
+    }
+
+    fn consider_at(&mut self, _: usize, target: &mut Cell, other_idx: usize, other: &Cell) {
+        for v in 1u8..=other.size() {
+            if other.value() == 0 && other.is_open(v) {
+                self.last[v as usize] = Some(other_idx);
+            }
+        }
+        self.consider(target, *other);
     }
 
     fn consider(&mut self, _target: &mut Cell, other: Cell) {
+        self.members += 1;
         if other.value() == 0 {
-            for v in 1u8..=9 {
+            for v in 1u8..=other.size() {
                 self.options[v as usize] += other.is_open(v) as u8;
             }
         }
     }
 
-    fn end(&self, target: &mut Cell) {
-        for v in 1u8..=9 {
+    fn end_at(&mut self, _: usize, target: &mut Cell) {
+        // Solved targets and targets outside of any subgroup are
+        // never touched.
+        if target.value() != 0 || self.members == 0 {
+            return;
+        }
+        for v in 1u8..=target.size() {
             if self.options[v as usize] == 0 && target.is_open(v) {
+                // Already single targets are left to Cell::solve.
+                if target.num_options() > 1 {
+                    self.changes.push(Change {
+                        index: self.target,
+                        kind: ChangeKind::Set(v),
+                    });
+                }
                 return target.single(v);
             }
         }
     }
+
+    fn last_changes(&self) -> &[Change] {
+        &self.changes
+    }
+}
+
+// +--------------------+
+// | NonConsecutiveRule |
+// +--------------------+
+
+// Orthogonally adjacent cells cannot hold consecutive values.  Equal
+// values are already ruled out by the row and column rules.
+#[derive(Clone)]
+pub struct NonConsecutiveRule {}
+
+// Name some variant collections use for the same constraint.
+pub type AntiConsecutiveRule = NonConsecutiveRule;
+
+impl NonConsecutiveRule {
+    pub fn new() -> Self {
+        NonConsecutiveRule {}
+    }
+}
+
+impl Default for NonConsecutiveRule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Rule for NonConsecutiveRule {
+    fn name(&self) -> &str {
+        "non_consecutive"
+    }
+
+    fn predicate(&self, target: usize, other: usize) -> bool {
+        adjacent(target, other)
+    }
+
+    fn consider(&mut self, target: &mut Cell, other: Cell) {
+        let v = other.value();
+        if v > 1 {
+            target.shut(v - 1);
+        }
+        if v != 0 && v < 9 {
+            target.shut(v + 1);
+        }
+    }
+}
+
+// +---------------+
+// | NakedPairRule |
+// +---------------+
+
+// Two other cells of the target's unit left with the same two
+// options take both values between them, so the target can have
+// neither.
+#[derive(Clone)]
+pub struct NakedPairRule {
+    // Options of the other cells with exactly two left.
+    pairs: Vec<u16>,
+    name: &'static str,
+    scope: Scope,
+    target: usize,
+    changes: Vec<Change>,
+}
+
+impl NakedPairRule {
+    fn with_scope(unit: Unit, scope: Scope) -> Self {
+        let name = match unit {
+            Unit::Row => "naked_pair_row",
+            Unit::Col => "naked_pair_col",
+            Unit::Square => "naked_pair_square",
+        };
+        NakedPairRule {
+            pairs: vec![],
+            name,
+            scope,
+            target: 0,
+            changes: vec![],
+        }
+    }
+
+    /// Rule for the rows, columns or squares.
+    pub fn for_unit(unit: Unit) -> Self {
+        Self::with_scope(unit, Scope::Fn(unit_predicate(unit)))
+    }
+
+    /// Rule for the rows, columns or boxes of a Grid.
+    pub fn for_grid(dims: Dimensions, unit: Unit) -> Self {
+        Self::with_scope(unit, Scope::Unit(dims, unit))
+    }
+
+    pub fn new_row() -> Self {
+        Self::for_unit(Unit::Row)
+    }
+
+    pub fn new_col() -> Self {
+        Self::for_unit(Unit::Col)
+    }
+
+    pub fn new_square() -> Self {
+        Self::for_unit(Unit::Square)
+    }
+}
+
+impl Rule for NakedPairRule {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn begin_pass(&mut self) {
+        self.changes.clear();
+    }
+
+    fn begin_at(&mut self, target: usize) {
+        self.pairs.clear();
+        self.target = target;
+    }
+
+    fn predicate(&self, target: usize, other: usize) -> bool {
+        self.scope.contains(target, other)
+    }
+
+    fn consider(&mut self, _target: &mut Cell, other: Cell) {
+        if other.value() == 0 && other.num_options() == 2 {
+            self.pairs.push(other.options());
+        }
+    }
+
+    fn end_at(&mut self, _: usize, target: &mut Cell) {
+        if target.value() != 0 {
+            return;
+        }
+        for (k, &mask) in self.pairs.iter().enumerate() {
+            if !self.pairs[k + 1..].contains(&mask) {
+                continue;
+            }
+            for v in (1u8..=target.size()).filter(|v| mask & 1 << (v - 1) != 0) {
+                if target.is_open(v) {
+                    target.shut(v);
+                    self.changes.push(Change {
+                        index: self.target,
+                        kind: ChangeKind::Eliminated(v),
+                    });
+                }
+            }
+        }
+    }
+
+    fn last_changes(&self) -> &[Change] {
+        &self.changes
+    }
+}
+
+// +----------+
+// | FishRule |
+// +----------+
+
+// If a value's options in size base lines (rows, or columns) fall
+// into exactly size cover lines (columns, or rows), each cover line
+// gets its value from one of those base lines, so the value can go
+// nowhere else in the cover lines.  X-Wing, Swordfish and Jellyfish
+// are sizes 2, 3 and 4.
+//
+// The rule looks at the whole grid for every target, which is the
+// only way to see every base line.
+#[derive(Clone)]
+pub struct FishRule {
+    size: usize,
+    by_row: bool,
+    name: &'static str,
+    // masks[v][line] has bit k set if value v is open at position k
+    // of base line line.
+    masks: [[u16; 9]; 10],
+    // Base lines where each value is placed, one bit per line.
+    placed: [u16; 10],
+    target: usize,
+    changes: Vec<Change>,
+}
+
+impl FishRule {
+    /// Fish of 2 to 4 lines, rows as base lines if by_row is set and
+    /// columns otherwise.
+    pub fn new(size: usize, by_row: bool) -> Self {
+        let name = match (size, by_row) {
+            (2, true) => "x_wing_row",
+            (2, false) => "x_wing_col",
+            (3, true) => "swordfish_row",
+            (3, false) => "swordfish_col",
+            (4, true) => "jellyfish_row",
+            (4, false) => "jellyfish_col",
+            _ => panic!("fish of size {}", size),
+        };
+        FishRule {
+            size,
+            by_row,
+            name,
+            masks: [[0; 9]; 10],
+            placed: [0; 10],
+            target: 0,
+            changes: vec![],
+        }
+    }
+
+    // Base line and position along it.
+    fn locate(&self, index: usize) -> (usize, usize) {
+        let (row, col) = (index / 9, index % 9);
+        if self.by_row {
+            (row, col)
+        } else {
+            (col, row)
+        }
+    }
+
+    fn record(&mut self, index: usize, cell: &Cell) {
+        let (line, pos) = self.locate(index);
+        let v = cell.value();
+        if v != 0 {
+            self.placed[v as usize] |= 1 << line;
+            return;
+        }
+        for v in 1u8..=9 {
+            if cell.is_open(v) {
+                self.masks[v as usize][line] |= 1 << pos;
+            }
+        }
+    }
+
+    // Some fish for value covers pos without using line as a base.
+    fn covers(&self, value: u8, line: usize, pos: usize) -> bool {
+        let masks = &self.masks[value as usize];
+        let bases: Vec<u16> = (0..9)
+            .filter(|x| *x != line && self.placed[value as usize] & 1 << x == 0)
+            .map(|x| masks[x])
+            .filter(|x| *x != 0 && x.count_ones() as usize <= self.size)
+            .collect();
+        fn pick(bases: &[u16], left: usize, union: u16, size: usize, pos: usize) -> bool {
+            if union.count_ones() as usize > size {
+                return false;
+            }
+            if left == 0 {
+                return union.count_ones() as usize == size && union & 1 << pos != 0;
+            }
+            (0..bases.len()).any(|k| pick(&bases[k + 1..], left - 1, union | bases[k], size, pos))
+        }
+        pick(&bases, self.size, 0, self.size, pos)
+    }
+}
+
+impl Rule for FishRule {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn begin_pass(&mut self) {
+        self.changes.clear();
+    }
+
+    fn begin_at(&mut self, target: usize) {
+        self.masks = [[0; 9]; 10];
+        self.placed = [0; 10];
+        self.target = target;
+    }
+
+    fn predicate(&self, _target: usize, _other: usize) -> bool {
+        true
+    }
+
+    fn consider_at(&mut self, _: usize, _: &mut Cell, other_idx: usize, other: &Cell) {
+        self.record(other_idx, other);
+    }
+
+    fn end_at(&mut self, _: usize, target: &mut Cell) {
+        if target.value() != 0 {
+            return;
+        }
+        self.record(self.target, target);
+        let (line, pos) = self.locate(self.target);
+        for v in 1u8..=9 {
+            if target.is_open(v) && self.covers(v, line, pos) {
+                target.shut(v);
+                self.changes.push(Change {
+                    index: self.target,
+                    kind: ChangeKind::Eliminated(v),
+                });
+            }
+        }
+    }
+
+    fn last_changes(&self) -> &[Change] {
+        &self.changes
+    }
+}
+
+// Named constructors for the fish sizes, all of them FishRule.
+pub struct XWingRule;
+pub struct SwordfishRule;
+pub struct JellyfishRule;
+
+impl XWingRule {
+    pub fn new_row() -> FishRule {
+        FishRule::new(2, true)
+    }
+
+    pub fn new_col() -> FishRule {
+        FishRule::new(2, false)
+    }
+}
+
+impl SwordfishRule {
+    pub fn new_row() -> FishRule {
+        FishRule::new(3, true)
+    }
+
+    pub fn new_col() -> FishRule {
+        FishRule::new(3, false)
+    }
+}
+
+impl JellyfishRule {
+    pub fn new_row() -> FishRule {
+        FishRule::new(4, true)
+    }
+
+    pub fn new_col() -> FishRule {
+        FishRule::new(4, false)
+    }
+}
+
+// +----------+
+// | Registry |
+// +----------+
+
+/// Fresh rule of the named kind.  Rules that take parameters, like
+/// cages and inequalities, can't be made from a name alone, and
+/// neither can unnamed ones.
+pub fn rule_by_name(name: &str) -> Option<Box<dyn Rule>> {
+    let rule: Box<dyn Rule> = match name {
+        "exclusion_row" => Box::new(ExclusionRule::new_row()),
+        "exclusion_col" => Box::new(ExclusionRule::new_col()),
+        "exclusion_square" => Box::new(ExclusionRule::new_square()),
+        "exclusion_diag_main" => Box::new(ExclusionRule::new_diag_main()),
+        "exclusion_diag_anti" => Box::new(ExclusionRule::new_diag_anti()),
+        "exclusion_window" => Box::new(ExclusionRule::new_window()),
+        "exclusion_anti_knight" => Box::new(ExclusionRule::new_anti_knight()),
+        "exclusion_anti_king" => Box::new(ExclusionRule::new_anti_king()),
+        "single_opt_row" => Box::new(SingleOptRule::new_row()),
+        "single_opt_col" => Box::new(SingleOptRule::new_col()),
+        "single_opt_square" => Box::new(SingleOptRule::new_square()),
+        "single_opt_diag_main" => Box::new(SingleOptRule::new_diag_main()),
+        "single_opt_diag_anti" => Box::new(SingleOptRule::new_diag_anti()),
+        "single_opt_window" => Box::new(SingleOptRule::new_window()),
+        "non_consecutive" => Box::new(NonConsecutiveRule::new()),
+        "naked_pair_row" => Box::new(NakedPairRule::new_row()),
+        "naked_pair_col" => Box::new(NakedPairRule::new_col()),
+        "naked_pair_square" => Box::new(NakedPairRule::new_square()),
+        "x_wing_row" => Box::new(XWingRule::new_row()),
+        "x_wing_col" => Box::new(XWingRule::new_col()),
+        "swordfish_row" => Box::new(SwordfishRule::new_row()),
+        "swordfish_col" => Box::new(SwordfishRule::new_col()),
+        "jellyfish_row" => Box::new(JellyfishRule::new_row()),
+        "jellyfish_col" => Box::new(JellyfishRule::new_col()),
+        _ => return None,
+    };
+    Some(rule)
+}
+
+impl Sudoku {
+    /// Names of the rules in use, in order.
+    pub fn rules_signature(&self) -> Vec<String> {
+        self.rules.iter().map(|x| x.name().to_string()).collect()
+    }
+
+    /// Add a fresh rule by name, see rule_by_name().  Returns false,
+    /// leaving the rules alone, if there's no such rule.
+    pub fn add_rule_by_name(&mut self, name: &str) -> bool {
+        match rule_by_name(name) {
+            Some(rule) => {
+                self.rules.push(rule);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+// +-----------+
+// | Rule sets |
+// +-----------+
+
+impl Sudoku {
+    /// Add exclusion and single option rules for rows, columns and
+    /// squares.
+    pub fn with_standard_rules(mut self) -> Self {
+        self.rules.push(Box::new(ExclusionRule::new_row()));
+        self.rules.push(Box::new(ExclusionRule::new_col()));
+        self.rules.push(Box::new(ExclusionRule::new_square()));
+        self.rules.push(Box::new(SingleOptRule::new_row()));
+        self.rules.push(Box::new(SingleOptRule::new_col()));
+        self.rules.push(Box::new(SingleOptRule::new_square()));
+        self
+    }
+
+    /// Standard rules plus both main diagonals (X-Sudoku).
+    pub fn with_x_rules(self) -> Self {
+        let mut s = self.with_standard_rules();
+        s.rules.push(Box::new(ExclusionRule::new_diag_main()));
+        s.rules.push(Box::new(ExclusionRule::new_diag_anti()));
+        s.rules.push(Box::new(SingleOptRule::new_diag_main()));
+        s.rules.push(Box::new(SingleOptRule::new_diag_anti()));
+        s
+    }
+
+    /// Standard rules plus the four extra windows (Hyper Sudoku).
+    pub fn with_hyper_rules(self) -> Self {
+        let mut s = self.with_standard_rules();
+        s.rules.push(Box::new(ExclusionRule::new_window()));
+        s.rules.push(Box::new(SingleOptRule::new_window()));
+        s
+    }
+
+    /// Standard rules plus no equal values a knight's move apart.
+    /// Knight moves don't form units, so there's no single option
+    /// counterpart.
+    pub fn with_anti_knight_rules(self) -> Self {
+        let mut s = self.with_standard_rules();
+        s.rules.push(Box::new(ExclusionRule::new_anti_knight()));
+        s
+    }
+
+    /// Standard rules plus no consecutive values next to each other.
+    pub fn with_non_consecutive_rules(self) -> Self {
+        let mut s = self.with_standard_rules();
+        s.rules.push(Box::new(NonConsecutiveRule::new()));
+        s
+    }
+
+    /// Standard rules plus no equal values a king's move apart.
+    pub fn with_anti_king_rules(self) -> Self {
+        let mut s = self.with_standard_rules();
+        s.rules.push(Box::new(ExclusionRule::new_anti_king()));
+        s
+    }
 }
 
 // +------+
@@ -131,7 +817,7 @@ impl Rule for SingleOptRule {
 mod test {
     use super::*;
     use crate::presets;
-    use crate::structure::{Solver, Sudoku};
+    use crate::structure::Solver;
 
     #[test]
     fn test_row_rule() {
@@ -180,32 +866,42 @@ mod test {
         assert_eq!(s.cells[59].value(), 8);
     }
 
+    // Lines of the candidates dump of b that differ from a.
+    fn changed_lines(a: &str, b: &Sudoku) -> Vec<String> {
+        a.lines()
+            .zip(b.candidates_string().lines())
+            .filter(|(x, y)| x != y)
+            .map(|(_, y)| y.to_string())
+            .collect()
+    }
+
     #[test]
     fn test_single_opt_square_rule() {
         let mut s = presets::load_easy();
         s.rules.push(Box::new(SingleOptRule::new_square()));
 
-        // Make sure it's still unsolvable.
+        // Make sure nothing changes anywhere.
+        let snapshot = s.candidates_string();
         assert_eq!(s.solve(), 0);
-        assert_eq!(s.cells[67].value(), 0);
+        assert!(changed_lines(&snapshot, &s).is_empty());
 
-        // Shut down the possibility of cell #59 to have a 4.
-        assert!(s.cells[59].is_open(4));
+        // Shut down the possibility of cell #59 to have a 4, still
+        // nothing to do.
         s.cells[59].shut(4);
-        assert_eq!(s.cells[59].is_open(4), false);
-
-        // Make sure it's still unsolvable.
+        let snapshot = s.candidates_string();
         assert_eq!(s.solve(), 0);
-        assert_eq!(s.cells[67].value(), 0);
+        assert!(changed_lines(&snapshot, &s).is_empty());
 
-        // Shut down the possibility of cell #68 to have a 4.
-        assert!(s.cells[68].is_open(4));
+        // Shut down the possibility of cell #68 to have a 4, now cell
+        // #67 is the last place for it and nothing else changes.
         s.cells[68].shut(4);
-        assert_eq!(s.cells[68].is_open(4), false);
-
-        // Make sure it's now solvable.
+        let snapshot = s.candidates_string();
         assert_eq!(s.solve(), 1);
-        assert_eq!(s.cells[67].value(), 4);
+        assert_eq!(changed_lines(&snapshot, &s), vec!["r8c5: =4"]);
+
+        // Snapshots load back as fixtures.
+        let fixture = Sudoku::from_candidates_string(&s.candidates_string()).unwrap();
+        assert_eq!(fixture.cells, s.cells);
     }
 
     #[test]
@@ -219,4 +915,421 @@ mod test {
         s.rules.push(Box::new(SingleOptRule::new_square()));
         assert_eq!(s.solve(), 43);
     }
+
+    #[test]
+    fn test_diagonal_predicates() {
+        assert!(same_diagonal_main(0, 80));
+        assert!(same_diagonal_main(10, 40));
+        assert!(!same_diagonal_main(0, 1));
+        assert!(!same_diagonal_main(1, 11));
+        assert!(same_diagonal_anti(8, 72));
+        assert!(same_diagonal_anti(16, 40));
+        assert!(!same_diagonal_anti(0, 40));
+        assert!(!same_diagonal_anti(7, 15));
+    }
+
+    #[test]
+    fn test_diagonal_rules_off_diagonal() {
+        let mut s = Sudoku::default();
+        s.cells[0].set(5);
+        s.rules.push(Box::new(ExclusionRule::new_diag_main()));
+        s.rules.push(Box::new(ExclusionRule::new_diag_anti()));
+        s.rules.push(Box::new(SingleOptRule::new_diag_main()));
+        s.rules.push(Box::new(SingleOptRule::new_diag_anti()));
+        assert_eq!(s.solve(), 0);
+
+        // Diagonal cells lose the 5, all others are left alone.
+        for i in 1..81 {
+            let on_diagonal = i / 9 == i % 9;
+            assert_eq!(s.cells[i].is_open(5), !on_diagonal, "i={}", i);
+            assert_eq!(s.cells[i].num_options(), 9 - on_diagonal as u32);
+        }
+    }
+
+    #[test]
+    fn test_x_rules() {
+        let mut s = presets::load_x().with_standard_rules();
+        s.solve();
+        assert!(!s.is_solved());
+
+        let mut s = presets::load_x().with_x_rules();
+        s.solve();
+        assert!(s.is_solved());
+        assert_eq!(s.cells[0].value(), 6);
+        assert_eq!(s.cells[80].value(), 8);
+    }
+
+    #[test]
+    fn test_single_opt_untallied_value() {
+        // Unsolved target with 5 open, none of the others with it.
+        let mut rule = SingleOptRule::new_row();
+        let mut target = Cell::default();
+        target.shut(9);
+        let mut other = Cell::default();
+        other.shut(5);
+        rule.begin_at(0);
+        for _ in 0..8 {
+            rule.consider(&mut target, other);
+        }
+        rule.end_at(0, &mut target);
+        assert_eq!(target.options(), 0b0_0001_0000);
+        assert_eq!(
+            rule.last_changes(),
+            &[Change {
+                index: 0,
+                kind: ChangeKind::Set(5)
+            }]
+        );
+
+        // Once every value has another place, the target keeps its
+        // options.
+        let mut target = Cell::default();
+        rule.begin_at(0);
+        rule.consider(&mut target, other);
+        rule.consider(&mut target, Cell::default());
+        rule.end_at(0, &mut target);
+        assert_eq!(target.num_options(), 9);
+    }
+
+    #[test]
+    fn test_single_opt_last_open() {
+        let mut rule = SingleOptRule::new_row();
+        let mut s = Sudoku::default();
+        s.cells[7].shut(3);
+        s.cells[8].set(4);
+        rule.begin_at(0);
+        for other in 1..9 {
+            let mut target = s.cells[0];
+            rule.consider_at(0, &mut target, other, &s.cells[other]);
+        }
+        assert_eq!(rule.last_open(3), Some(6));
+        assert_eq!(rule.last_open(4), Some(7));
+        assert_eq!(rule.last_open(5), Some(7));
+
+        rule.begin_at(1);
+        assert_eq!(rule.last_open(5), None);
+    }
+
+    #[test]
+    fn test_exclusion_last_changes() {
+        let mut s = Sudoku::default();
+        s.cells[0].set(5);
+        s.cells[10].shut(5);
+        s.rules.push(Box::new(ExclusionRule::new_row()));
+        s.rules.push(Box::new(ExclusionRule::new_square()));
+        assert!(s.rules[0].last_changes().is_empty());
+        s.solve_step();
+
+        let want: Vec<Change> = (1..9)
+            .map(|index| Change {
+                index,
+                kind: ChangeKind::Eliminated(5),
+            })
+            .collect();
+        assert_eq!(s.rules[0].last_changes(), &want[..]);
+        // The row rule got to cells 1 and 2 first and cell 10 had no
+        // 5 left to shut.
+        let have: Vec<usize> = s.rules[1].last_changes().iter().map(|x| x.index).collect();
+        assert_eq!(have, vec![9, 11, 18, 19, 20]);
+
+        // Nothing left to do on the next pass.
+        s.solve_step();
+        assert!(s.rules[0].last_changes().is_empty());
+    }
+
+    #[test]
+    fn test_single_opt_last_changes() {
+        let mut s = Sudoku::default();
+        for i in 1..9 {
+            s.cells[i].shut(5);
+        }
+        s.rules.push(Box::new(SingleOptRule::new_row()));
+        let snapshot = s.candidates_string();
+        assert_eq!(s.solve_step(), 1);
+        assert_eq!(changed_lines(&snapshot, &s), vec!["r1c1: =5"]);
+        let want = Change {
+            index: 0,
+            kind: ChangeKind::Set(5),
+        };
+        assert_eq!(s.rules[0].last_changes(), &[want]);
+    }
+
+    #[test]
+    fn test_naked_pair_rule() {
+        // r1c1 and r1c2 are down to 1 and 2.
+        let mut s = Sudoku::default();
+        for &i in &[0, 1] {
+            for v in 3u8..=9 {
+                s.cells[i].shut(v);
+            }
+        }
+        s.rules.push(Box::new(NakedPairRule::new_row()));
+        s.solve_step();
+        for i in 2..9 {
+            assert_eq!(s.cells[i].options(), 0b1_1111_1100, "i={}", i);
+        }
+        assert_eq!(s.cells[0].options(), 0b0_0000_0011);
+        assert_eq!(s.cells[9].num_options(), 9);
+        assert_eq!(s.rules[0].last_changes().len(), 14);
+
+        // A single cell with two options is no pair.
+        let mut s = Sudoku::default();
+        for v in 3u8..=9 {
+            s.cells[0].shut(v);
+        }
+        s.rules.push(Box::new(NakedPairRule::new_square()));
+        s.solve_step();
+        assert!(s.rules[0].last_changes().is_empty());
+    }
+
+    // Value 1 only open in columns 3 and 7 of rows 1 and 5.
+    fn x_wing_board() -> Sudoku {
+        let mut s = Sudoku::default();
+        for &row in &[0, 4] {
+            for col in (0..9).filter(|c| *c != 2 && *c != 6) {
+                s.cells[row * 9 + col].shut(1);
+            }
+        }
+        s
+    }
+
+    #[test]
+    fn test_x_wing_rule() {
+        let mut a = x_wing_board();
+        a.rules.push(Box::new(XWingRule::new_row()));
+        let snapshot = a.candidates_string();
+        assert_eq!(a.solve_step(), 0);
+
+        let mut want: Vec<String> = vec![];
+        for row in (0..9).filter(|r| *r != 0 && *r != 4) {
+            for &col in &[2, 6] {
+                want.push(format!("r{}c{}: 23456789", row + 1, col + 1));
+            }
+        }
+        want.sort();
+        let mut have = changed_lines(&snapshot, &a);
+        have.sort();
+        assert_eq!(have, want);
+
+        // The generic rule does exactly the same.
+        let mut b = x_wing_board();
+        b.rules.push(Box::new(FishRule::new(2, true)));
+        b.solve_step();
+        assert_eq!(a.cells, b.cells);
+        assert_eq!(a.rules[0].last_changes(), b.rules[0].last_changes());
+        assert_eq!(a.rules[0].last_changes().len(), 14);
+
+        // Columns as base lines see no fish here.
+        let mut c = x_wing_board();
+        c.rules.push(Box::new(XWingRule::new_col()));
+        c.solve_step();
+        assert!(c.rules[0].last_changes().is_empty());
+    }
+
+    #[test]
+    fn test_swordfish_rule() {
+        // Value 9 confined to rows 1, 4 and 7 in columns 1, 5 and 9,
+        // two options per column.
+        let mut s = Sudoku::default();
+        let keep = [(0, 0), (3, 0), (3, 4), (6, 4), (0, 8), (6, 8)];
+        for &col in &[0, 4, 8] {
+            for row in 0..9 {
+                if !keep.contains(&(row, col)) {
+                    s.cells[row * 9 + col].shut(9);
+                }
+            }
+        }
+        // An x-wing has nothing to grab.
+        let mut x = s.duplicate();
+        x.rules.push(Box::new(XWingRule::new_col()));
+        x.solve_step();
+        assert!(x.rules[0].last_changes().is_empty());
+
+        s.rules.push(Box::new(SwordfishRule::new_col()));
+        s.solve_step();
+        let have: Vec<usize> = s.rules[0].last_changes().iter().map(|x| x.index).collect();
+        let want: Vec<usize> = [0, 3, 6]
+            .iter()
+            .flat_map(|row| (0..9).filter(|c| c % 4 != 0).map(move |c| row * 9 + c))
+            .collect();
+        assert_eq!(have, want);
+    }
+
+    #[test]
+    fn test_rule_by_name() {
+        let names = [
+            "exclusion_row",
+            "exclusion_anti_king",
+            "single_opt_square",
+            "single_opt_window",
+            "non_consecutive",
+            "naked_pair_square",
+            "x_wing_row",
+            "jellyfish_col",
+        ];
+        for name in names.iter() {
+            assert_eq!(rule_by_name(name).unwrap().name(), *name);
+        }
+        assert!(rule_by_name("cage").is_none());
+        assert!(rule_by_name("").is_none());
+    }
+
+    #[test]
+    fn test_add_rule_by_name() {
+        let mut want = presets::load_easy();
+        want.rules.push(Box::new(ExclusionRule::new_row()));
+        want.cells[59].set(8);
+        want.cells[61].set(3);
+        assert_eq!(want.solve(), 1);
+
+        let mut s = presets::load_easy();
+        assert!(s.add_rule_by_name("exclusion_row"));
+        assert!(!s.add_rule_by_name("exclusion_rows"));
+        assert_eq!(s.rules_signature(), vec!["exclusion_row"]);
+        s.cells[59].set(8);
+        s.cells[61].set(3);
+        assert_eq!(s.solve(), 1);
+        assert_eq!(s.cells, want.cells);
+
+        let s = Sudoku::default().with_standard_rules();
+        let mut copy = Sudoku::default();
+        for name in s.rules_signature() {
+            assert!(copy.add_rule_by_name(&name));
+        }
+        assert_eq!(copy.rules_signature(), s.rules_signature());
+    }
+
+    #[test]
+    fn test_window_of() {
+        let windows = "
+            . . . . . . . . .
+            . 0 0 0 . 1 1 1 .
+            . 0 0 0 . 1 1 1 .
+            . 0 0 0 . 1 1 1 .
+            . . . . . . . . .
+            . 2 2 2 . 3 3 3 .
+            . 2 2 2 . 3 3 3 .
+            . 2 2 2 . 3 3 3 .
+            . . . . . . . . .";
+        let want: Vec<Option<usize>> = windows.split_whitespace().map(|x| x.parse().ok()).collect();
+        assert_eq!(want.len(), 81);
+        for (index, w) in want.iter().enumerate() {
+            assert_eq!(window_of(index), *w, "index={}", index);
+        }
+    }
+
+    #[test]
+    fn test_same_window() {
+        assert!(same_window(10, 30));
+        assert!(same_window(50, 70));
+        assert!(!same_window(10, 14));
+        assert!(!same_window(0, 1));
+        assert!(!same_window(4, 13));
+    }
+
+    #[test]
+    fn test_hyper_rules() {
+        let mut s = presets::load_hyper().with_standard_rules();
+        s.solve();
+        assert!(!s.is_solved());
+
+        let mut s = presets::load_hyper().with_hyper_rules();
+        s.solve();
+        assert!(s.is_solved());
+        assert_eq!(s.cells[1].value(), 9);
+        assert_eq!(s.cells[80].value(), 2);
+    }
+
+    #[test]
+    fn test_knight_move() {
+        assert!(same_knight_move(0, 11));
+        assert!(same_knight_move(0, 19));
+        assert!(!same_knight_move(0, 10));
+        assert!(!same_knight_move(0, 7)); // wraps around to row 1
+        assert!(same_knight_move(8, 15));
+        assert!(same_knight_move(8, 25));
+        assert!(!same_knight_move(8, 18)); // wraps around to row 2
+        assert!(!same_knight_move(8, 10));
+        assert!(same_knight_move(80, 69));
+        assert!(same_knight_move(80, 61));
+        assert!(!same_knight_move(80, 71));
+        assert_eq!((0..81).filter(|x| same_knight_move(40, *x)).count(), 8);
+    }
+
+    #[test]
+    fn test_king_move() {
+        assert!(same_king_move(0, 1));
+        assert!(same_king_move(0, 10));
+        assert!(!same_king_move(0, 0));
+        assert!(!same_king_move(0, 8));
+        assert!(same_king_move(8, 16));
+        assert!(!same_king_move(8, 9)); // wraps around to row 1
+        assert!(same_king_move(80, 70));
+        assert!(!same_king_move(80, 72));
+        assert_eq!((0..81).filter(|x| same_king_move(40, *x)).count(), 8);
+    }
+
+    #[test]
+    fn test_anti_knight_rules() {
+        let mut s = presets::load_anti_knight().with_standard_rules();
+        s.solve();
+        assert!(!s.is_solved());
+
+        let mut s = presets::load_anti_knight().with_anti_knight_rules();
+        s.solve();
+        assert!(s.is_solved());
+        assert_eq!(s.cells[0].value(), 2);
+        assert_eq!(s.cells[80].value(), 4);
+    }
+
+    #[test]
+    fn test_adjacent() {
+        assert!(adjacent(0, 1));
+        assert!(adjacent(0, 9));
+        assert!(!adjacent(0, 10));
+        assert!(!adjacent(8, 9)); // wraps around to row 1
+        assert!(adjacent(80, 71));
+        assert!(!adjacent(40, 40));
+        assert_eq!((0..81).filter(|x| adjacent(40, *x)).count(), 4);
+    }
+
+    #[test]
+    fn test_non_consecutive_rule() {
+        let mut s = Sudoku::default();
+        s.cells[40].set(5);
+        s.rules.push(Box::new(NonConsecutiveRule::new()));
+        assert_eq!(s.solve(), 0);
+        for &i in &[31, 39, 41, 49] {
+            assert!(!s.cells[i].is_open(4), "i={}", i);
+            assert!(!s.cells[i].is_open(6), "i={}", i);
+            assert_eq!(s.cells[i].num_options(), 7);
+        }
+        assert_eq!(s.cells[30].num_options(), 9);
+    }
+
+    #[test]
+    fn test_anti_consecutive_rule_corner() {
+        // A corner has only two orthogonal neighbours.
+        let mut s = Sudoku::default();
+        s.cells[0].set(5);
+        s.rules.push(Box::new(AntiConsecutiveRule::new()));
+        s.solve_step();
+        for &i in &[1, 9] {
+            assert_eq!(s.cells[i].options(), 0b1_1101_0111, "i={}", i);
+        }
+        assert_eq!(s.cells[10].num_options(), 9);
+    }
+
+    #[test]
+    fn test_non_consecutive_rules() {
+        let mut s = presets::load_non_consecutive().with_standard_rules();
+        s.solve();
+        assert!(!s.is_solved());
+
+        let mut s = presets::load_non_consecutive().with_non_consecutive_rules();
+        s.solve();
+        assert!(s.is_solved());
+        assert_eq!(s.cells[1].value(), 6);
+        assert_eq!(s.cells[80].value(), 5);
+    }
 }
@@ -0,0 +1,416 @@
+use crate::structure::{Cell, Solver, Sudoku};
+use alloc::{
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::convert::TryFrom;
+use core::fmt::{Display, Error, Formatter};
+use core::str::FromStr;
+
+// +--------+
+// | Layout |
+// +--------+
+
+/// Side of the square character layout holding all five grids.
+pub const SIDE: usize = 21;
+
+/// Index of the center grid, the corners come in reading order
+/// around it: top left, top right, bottom left and bottom right.
+pub const CENTER: usize = 2;
+
+// Row and column of the top left cell of each grid in the layout.
+const OFFSETS: [(usize, usize); 5] = [(0, 0), (0, 12), (6, 6), (12, 0), (12, 12)];
+
+/// Grid and cell index of each grid covering the given position of
+/// the layout.  Unused positions are covered by none, the corner
+/// boxes of the center grid by two.
+pub fn locate(row: usize, col: usize) -> Vec<(usize, usize)> {
+    OFFSETS
+        .iter()
+        .enumerate()
+        .filter(|(_, (r, c))| (*r..r + 9).contains(&row) && (*c..c + 9).contains(&col))
+        .map(|(grid, (r, c))| (grid, (row - r) * 9 + col - c))
+        .collect()
+}
+
+/// Two (grid, cell index) pairs that are the same cell of the layout.
+pub type Overlap = ((usize, usize), (usize, usize));
+
+// Cells shared between a corner grid and the center grid.
+fn overlaps() -> Vec<Overlap> {
+    let mut xs = vec![];
+    for row in 0..SIDE {
+        for col in 0..SIDE {
+            if let [a, b] = locate(row, col)[..] {
+                xs.push((a, b));
+            }
+        }
+    }
+    xs
+}
+
+// +---------+
+// | Samurai |
+// +---------+
+
+#[derive(Debug, PartialEq)]
+pub enum SamuraiError {
+    // Layout isn't 21 lines long.
+    Lines(usize),
+    // Character at (row, col) that doesn't belong there: anything but
+    // a cell in a grid, or anything but whitespace outside of them.
+    Char(usize, usize),
+    // Twin cells at (row, col) given different values.
+    Conflict(usize, usize),
+    // Grid with repeated givens.
+    Invalid(usize),
+}
+
+/// Five overlapping grids, each corner grid sharing its inner corner
+/// box with the center grid.
+pub struct Samurai {
+    pub grids: [Sudoku; 5],
+    overlaps: Vec<Overlap>,
+}
+
+impl Samurai {
+    /// Join five grids, copying givens across the shared boxes.
+    pub fn new(grids: [Sudoku; 5]) -> Result<Self, SamuraiError> {
+        let mut x = Samurai {
+            grids,
+            overlaps: overlaps(),
+        };
+        for &((a, i), (b, j)) in &x.overlaps {
+            let (p, q) = (x.grids[a].cells[i].value(), x.grids[b].cells[j].value());
+            if p != 0 && q != 0 && p != q {
+                let (r, c) = OFFSETS[a];
+                return Err(SamuraiError::Conflict(r + i / 9, c + i % 9));
+            }
+        }
+        x.sync();
+        Ok(x)
+    }
+
+    /// Cells shared between two grids.
+    pub fn overlaps(&self) -> &[Overlap] {
+        &self.overlaps
+    }
+
+    /// Add the standard rules to each of the grids.
+    pub fn with_standard_rules(self) -> Self {
+        let [a, b, c, d, e] = self.grids;
+        Samurai {
+            grids: [
+                a.with_standard_rules(),
+                b.with_standard_rules(),
+                c.with_standard_rules(),
+                d.with_standard_rules(),
+                e.with_standard_rules(),
+            ],
+            overlaps: self.overlaps,
+        }
+    }
+
+    /// Set a cell of a grid, and of its twin if the cell is shared.
+    pub fn set(&mut self, grid: usize, index: usize, value: u8) {
+        self.grids[grid].cells[index].set(value);
+        self.sync();
+    }
+
+    /// Cell at the given position of the layout, if any.
+    pub fn cell(&self, row: usize, col: usize) -> Option<Cell> {
+        locate(row, col)
+            .first()
+            .map(|&(grid, index)| self.grids[grid].cells[index])
+    }
+
+    pub fn is_solved(&self) -> bool {
+        self.grids.iter().all(Sudoku::is_solved)
+    }
+
+    /// Every grid is valid and twin cells agree.
+    pub fn is_valid(&self) -> bool {
+        self.grids.iter().all(Sudoku::is_valid)
+            && self.overlaps.iter().all(|&((a, i), (b, j))| {
+                self.grids[a].cells[i].value() == self.grids[b].cells[j].value()
+            })
+    }
+
+    /// Number of solved cells, shared cells counted once.
+    pub fn num_solved(&self) -> usize {
+        let all: usize = self.grids.iter().map(Sudoku::num_solved).sum();
+        let shared = self
+            .overlaps
+            .iter()
+            .filter(|&&((a, i), _)| self.grids[a].cells[i].value() != 0)
+            .count();
+        all - shared
+    }
+
+    // Make twin cells agree: a value set on one side is set on the
+    // other, otherwise both keep only the options open in both.
+    // Conflicting values are left for is_valid() to report.
+    fn sync(&mut self) {
+        for &((a, i), (b, j)) in &self.overlaps {
+            let (x, y) = (self.grids[a].cells[i], self.grids[b].cells[j]);
+            match (x.value(), y.value()) {
+                (0, 0) => {
+                    for v in 1u8..=9 {
+                        if !x.is_open(v) || !y.is_open(v) {
+                            self.grids[a].cells[i].shut(v);
+                            self.grids[b].cells[j].shut(v);
+                        }
+                    }
+                }
+                (0, v) => self.grids[a].cells[i].set(v),
+                (v, 0) => self.grids[b].cells[j].set(v),
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Samurai {
+    /// Solve each grid in turn and share what was found through the
+    /// overlaps, until a round changes nothing.  Returns the number of
+    /// newly solved cells of the layout.  Not Solver::solve(), the
+    /// layout has more cells than a u8 counts.
+    pub fn solve(&mut self) -> usize {
+        let before = self.num_solved();
+        loop {
+            let old: Vec<[Cell; 81]> = self.grids.iter().map(|x| x.cells).collect();
+            for grid in self.grids.iter_mut() {
+                grid.solve();
+            }
+            self.sync();
+            if self.is_solved() || self.grids.iter().zip(&old).all(|(x, y)| x.cells == *y) {
+                break;
+            }
+        }
+        self.num_solved() - before
+    }
+
+    /// Same as parse(), but unsolved cells start with the options
+    /// their peers in every grid leave, see Sudoku::fix_candidates().
+    pub fn parse_fixed(s: &str) -> Result<Samurai, SamuraiError> {
+        let mut x: Samurai = s.parse()?;
+        for grid in x.grids.iter_mut() {
+            let _ = grid.fix_candidates();
+        }
+        x.sync();
+        Ok(x)
+    }
+}
+
+impl Display for Samurai {
+    /// The 21x21 layout, dots for empty cells and spaces outside of
+    /// the grids.
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        let lines: Vec<String> = (0..SIDE)
+            .map(|row| {
+                let line: String = (0..SIDE)
+                    .map(|col| match self.cell(row, col) {
+                        Some(x) if x.value() != 0 => (b'0' + x.value()) as char,
+                        Some(_) => '.',
+                        None => ' ',
+                    })
+                    .collect();
+                line.trim_end().to_string()
+            })
+            .collect();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+impl FromStr for Samurai {
+    type Err = SamuraiError;
+
+    // One character per cell, as produced by Display.  Blank lines are
+    // skipped and lines may be cut short after the last grid.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lines: Vec<Vec<char>> = s
+            .lines()
+            .filter(|x| !x.trim().is_empty())
+            .map(|x| x.trim_end().chars().collect())
+            .collect();
+        if lines.len() != SIDE {
+            return Err(SamuraiError::Lines(lines.len()));
+        }
+
+        let mut grids: [Sudoku; 5] = Default::default();
+        for (row, line) in lines.iter().enumerate() {
+            if line.len() > SIDE {
+                return Err(SamuraiError::Char(row, SIDE));
+            }
+            for col in 0..SIDE {
+                let c = line.get(col).copied().unwrap_or(' ');
+                let at = locate(row, col);
+                if at.is_empty() {
+                    if !c.is_whitespace() {
+                        return Err(SamuraiError::Char(row, col));
+                    }
+                    continue;
+                }
+                // Spaces mark the unused corners, so they can't stand
+                // for empty cells here.
+                let cell = match c {
+                    ' ' => None,
+                    _ => Cell::try_from(c).ok(),
+                };
+                let cell = cell.ok_or(SamuraiError::Char(row, col))?;
+                for (grid, index) in at {
+                    grids[grid].cells[index] = cell;
+                }
+            }
+        }
+
+        if let Some(grid) = grids.iter().position(|x| !x.is_valid()) {
+            return Err(SamuraiError::Invalid(grid));
+        }
+        for grid in grids.iter_mut() {
+            grid.mark_givens();
+        }
+        Samurai::new(grids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PUZZLE: &str = "
+.....7...   .........
+3.6.8..2.   ..5..8.9.
+18....67.   9.....3.8
+4..7....1   8..9.5..1
+....9....   .........
+..8....6.   ..1.2.6..
+.....1...27...9.47...
+...6582..5...8.6...3.
+.2.......3.......3.1.
+      ..8.4.7..
+      .....5...
+      ......9..
+7........9.....1...52
+..4................4.
+...3.......6.2..4.68.
+.236.....   .3..54...
+9..47....   ...369.2.
+....5....   .....8.6.
+..279....   7........
+......8..   14..2..9.
+8.9....51   .5.......
+";
+
+    const SOLUTION: &str = "
+254367198   418539762
+376189425   365278194
+189542673   927416358
+492736581   843965271
+617895342   296781543
+538214769   751324689
+763921854271639147825
+941658237569184652937
+825473916384572893416
+      598642713
+      361795248
+      742138956
+736924185923467183952
+594817623417895672143
+281365479856321945687
+423689517   236754819
+915473268   518369724
+678152934   974218365
+152798346   782596431
+367541892   143827596
+849236751   659431278
+";
+
+    #[test]
+    fn test_locate() {
+        assert_eq!(locate(0, 0), vec![(0, 0)]);
+        assert_eq!(locate(0, 10), vec![]);
+        assert_eq!(locate(6, 6), vec![(0, 60), (CENTER, 0)]);
+        assert_eq!(locate(14, 14), vec![(CENTER, 80), (4, 20)]);
+        assert_eq!(locate(10, 10), vec![(CENTER, 40)]);
+        assert_eq!(overlaps().len(), 36);
+    }
+
+    #[test]
+    fn test_parse_display() {
+        let s: Samurai = PUZZLE.parse().unwrap();
+        assert_eq!(s.to_string(), PUZZLE.trim());
+        assert_eq!(s.num_solved(), 94);
+        // Shared givens show up in both grids.
+        assert_eq!(s.grids[0].cells[69].value(), 2);
+        assert_eq!(s.grids[CENTER].cells[9].value(), 2);
+        assert!(s.grids[0].givens[69] && s.grids[CENTER].givens[9]);
+        assert!(!s.grids[0].givens[0]);
+    }
+
+    #[test]
+    fn test_parse_fixed() {
+        let s = Samurai::parse_fixed(PUZZLE).unwrap();
+        assert_eq!(s.to_string(), PUZZLE.trim());
+        assert!(s.grids[0].givens[69]);
+        // r1c1 of the top left grid sees 3, 1 and 4 in its column and
+        // 7 in its row, among others.
+        let corner = s.grids[0].cells[0];
+        assert!(corner.num_options() < 9);
+        for v in [1, 3, 4, 7] {
+            assert!(!corner.is_open(v));
+        }
+        // The twins of a shared cell agree on their options.
+        assert_eq!(s.grids[0].cells[60], s.grids[CENTER].cells[0]);
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert_eq!("".parse::<Samurai>().err(), Some(SamuraiError::Lines(0)));
+
+        let mut lines: Vec<String> = PUZZLE.trim().lines().map(String::from).collect();
+        lines[0].replace_range(10..11, "1");
+        let unused = lines.join("\n").parse::<Samurai>();
+        assert_eq!(unused.err(), Some(SamuraiError::Char(0, 10)));
+
+        let mut lines: Vec<String> = PUZZLE.trim().lines().map(String::from).collect();
+        lines[1].replace_range(1..2, " ");
+        let hole = lines.join("\n").parse::<Samurai>();
+        assert_eq!(hole.err(), Some(SamuraiError::Char(1, 1)));
+
+        // The 7 in row 13 repeats the 7 in the first column of the
+        // bottom left grid.
+        let mut lines: Vec<String> = PUZZLE.trim().lines().map(String::from).collect();
+        lines[13].replace_range(0..1, "7");
+        let repeated = lines.join("\n").parse::<Samurai>();
+        assert_eq!(repeated.err(), Some(SamuraiError::Invalid(3)));
+    }
+
+    #[test]
+    fn test_new_conflict() {
+        let mut grids: [Sudoku; 5] = Default::default();
+        grids[0].cells[80].set(1);
+        grids[CENTER].cells[20].set(2);
+        let result = Samurai::new(grids);
+        assert_eq!(result.err(), Some(SamuraiError::Conflict(8, 8)));
+    }
+
+    #[test]
+    fn test_set_shared() {
+        let mut s = Samurai::new(Default::default()).unwrap();
+        s.set(4, 0, 5);
+        assert_eq!(s.grids[CENTER].cells[60].value(), 5);
+        assert_eq!(s.cell(12, 12).unwrap().value(), 5);
+        assert_eq!(s.num_solved(), 1);
+    }
+
+    #[test]
+    fn test_samurai() {
+        let mut s = PUZZLE.parse::<Samurai>().unwrap().with_standard_rules();
+        assert_eq!(s.solve(), 369 - 94);
+        assert!(s.is_solved());
+        assert!(s.is_valid());
+        assert_eq!(s.to_string(), SOLUTION.trim());
+    }
+}
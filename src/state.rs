@@ -0,0 +1,219 @@
+use crate::rules::rule_by_name;
+use crate::structure::{Cell, Sudoku};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+// Checkpoints of a half solved board:
+//
+//     sudoku-state 1
+//     values 530070000...   81 digits, 0 for unsolved cells
+//     options 0000001ff...  81 masks of 3 hex digits, bit 0 is 1
+//     givens 110010000...   81 flags
+//     rules exclusion_row exclusion_col ...
+
+const HEADER: &str = "sudoku-state 1";
+
+#[derive(Debug, PartialEq)]
+pub enum StateError {
+    // Missing or unknown version header.
+    Header,
+    // Missing or malformed line, by its keyword.
+    Line(&'static str),
+    // Cell with a value and open options too, or a given that isn't
+    // solved.
+    Inconsistent(usize),
+    // Rule the registry can't make from its name.
+    UnknownRule(String),
+    // Rule without a name, by its position, which can't be saved.
+    UnnamedRule(usize),
+}
+
+// Field following the keyword of a line.
+fn field<'a>(line: Option<&'a str>, keyword: &'static str) -> Result<&'a str, StateError> {
+    let line = line.ok_or(StateError::Line(keyword))?;
+    if line == keyword {
+        return Ok("");
+    }
+    line.strip_prefix(keyword)
+        .and_then(|x| x.strip_prefix(' '))
+        .ok_or(StateError::Line(keyword))
+}
+
+// Exactly 81 fields of the given width, each parsed by f.
+fn fields<T>(
+    s: &str,
+    width: usize,
+    keyword: &'static str,
+    f: impl Fn(&str) -> Option<T>,
+) -> Result<Vec<T>, StateError> {
+    if !s.is_ascii() || s.len() != 81 * width {
+        return Err(StateError::Line(keyword));
+    }
+    (0..81)
+        .map(|i| f(&s[i * width..(i + 1) * width]).ok_or(StateError::Line(keyword)))
+        .collect()
+}
+
+impl Sudoku {
+    /// Everything needed to pick up solving where it was left:
+    /// values, options, givens and the names of the rules.  Only
+    /// rules known to rules::rule_by_name() can be restored, and
+    /// rules without a name can't even be written down.
+    pub fn to_state_string(&self) -> Result<String, StateError> {
+        if let Some(k) = self.rules.iter().position(|x| x.name().is_empty()) {
+            return Err(StateError::UnnamedRule(k));
+        }
+        let values: String = self
+            .cells
+            .iter()
+            .map(|x| (b'0' + x.value()) as char)
+            .collect();
+        let options: String = self
+            .cells
+            .iter()
+            .map(|x| format!("{:03x}", x.options()))
+            .collect();
+        let givens: String = self
+            .givens
+            .iter()
+            .map(|x| if *x { '1' } else { '0' })
+            .collect();
+        let rules: Vec<&str> = self.rules.iter().map(|x| x.name()).collect();
+        let lines = [
+            HEADER.to_string(),
+            format!("values {}", values),
+            format!("options {}", options),
+            format!("givens {}", givens),
+            format!("rules {}", rules.join(" ")),
+        ];
+        let mut s = lines.join("\n");
+        s.push('\n');
+        Ok(s)
+    }
+
+    /// Parse what to_state_string() produces, rejecting cells whose
+    /// value and options contradict each other.
+    pub fn from_state_string(s: &str) -> Result<Sudoku, StateError> {
+        let mut lines = s.lines().map(str::trim_end).filter(|x| !x.is_empty());
+        if lines.next() != Some(HEADER) {
+            return Err(StateError::Header);
+        }
+        let values = fields(field(lines.next(), "values")?, 1, "values", |x| {
+            x.parse::<u8>().ok()
+        })?;
+        let options = fields(field(lines.next(), "options")?, 3, "options", |x| {
+            u16::from_str_radix(x, 16).ok().filter(|m| *m <= 0x1ff)
+        })?;
+        let givens = fields(field(lines.next(), "givens")?, 1, "givens", |x| match x {
+            "0" => Some(false),
+            "1" => Some(true),
+            _ => None,
+        })?;
+        let names = field(lines.next(), "rules")?;
+        if lines.next().is_some() {
+            return Err(StateError::Line("rules"));
+        }
+
+        let mut sudoku = Sudoku::default();
+        for i in 0..81 {
+            let (v, mask) = (values[i], options[i]);
+            let cell = &mut sudoku.cells[i];
+            if v != 0 {
+                if mask != 0 {
+                    return Err(StateError::Inconsistent(i));
+                }
+                cell.set(v);
+            } else {
+                if givens[i] {
+                    return Err(StateError::Inconsistent(i));
+                }
+                *cell = Cell::default();
+                for v in (1u8..=9).filter(|v| mask & 1 << (v - 1) == 0) {
+                    cell.shut(v);
+                }
+            }
+            sudoku.givens[i] = givens[i];
+        }
+        for name in names.split_whitespace() {
+            let rule =
+                rule_by_name(name).ok_or_else(|| StateError::UnknownRule(name.to_string()))?;
+            sudoku.rules.push(rule);
+        }
+        Ok(sudoku)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::presets;
+    use crate::structure::{Rule, Solver};
+    use alloc::boxed::Box;
+
+    #[test]
+    fn test_state_round_trip() {
+        let mut want = presets::load_hard().with_standard_rules();
+        want.solve();
+
+        let mut s = presets::load_hard().with_standard_rules();
+        s.solve_step();
+        s.solve_step();
+        assert!(!s.is_solved());
+        let state = s.to_state_string().unwrap();
+        let mut restored = Sudoku::from_state_string(&state).unwrap();
+        assert_eq!(restored.cells, s.cells);
+        assert_eq!(restored.givens, s.givens);
+        assert_eq!(restored.to_state_string(), Ok(state));
+
+        restored.solve();
+        assert_eq!(restored.cells, want.cells);
+    }
+
+    #[test]
+    fn test_state_invalid() {
+        let state = presets::load_easy()
+            .with_standard_rules()
+            .to_state_string()
+            .unwrap();
+        let lines: Vec<&str> = state.lines().collect();
+        let with = |i: usize, line: &str| {
+            let mut xs = lines.clone();
+            xs[i] = line;
+            Sudoku::from_state_string(&xs.join("\n")).err()
+        };
+
+        assert_eq!(with(0, "sudoku-state 2"), Some(StateError::Header));
+        assert_eq!(with(1, "values 123"), Some(StateError::Line("values")));
+        assert_eq!(with(4, "rule"), Some(StateError::Line("rules")));
+        assert_eq!(
+            with(4, "rules exclusion_row cage"),
+            Some(StateError::UnknownRule("cage".to_string()))
+        );
+
+        // Cell 0 holds a 3, it can't have open options too.
+        let options = format!("options 001{}", &lines[2]["options ".len() + 3..]);
+        assert_eq!(with(2, &options), Some(StateError::Inconsistent(0)));
+        // Cell 2 is empty, it can't be a given.
+        let givens = format!("givens 111{}", &lines[3]["givens ".len() + 3..]);
+        assert_eq!(with(3, &givens), Some(StateError::Inconsistent(2)));
+    }
+
+    // Rule that never got a name.
+    #[derive(Clone)]
+    struct Unnamed;
+
+    impl Rule for Unnamed {
+        fn predicate(&self, _: usize, _: usize) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn test_state_unnamed_rule() {
+        let mut s = presets::load_easy().with_standard_rules();
+        s.rules.push(Box::new(Unnamed));
+        assert_eq!(s.to_state_string(), Err(StateError::UnnamedRule(6)));
+        assert_eq!(s.rules_signature()[6], "");
+    }
+}
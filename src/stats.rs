@@ -0,0 +1,188 @@
+use crate::structure::{ChangeKind, PassObserver, StepResult, Sudoku};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{Display, Error, Formatter};
+use core::time::Duration;
+
+/// What a single rule contributed to a solve.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RuleStats {
+    // Cells the rule considered, over all targets and passes.
+    pub invocations: usize,
+    // Values the rule left as the only option of a cell.
+    pub placements: usize,
+    // Options the rule shut.
+    pub eliminations: usize,
+}
+
+/// Numbers collected by Sudoku::solve_with_stats().
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SolveStats {
+    pub passes: usize,
+    // Cells solved, whichever rule narrowed them down.
+    pub placements: usize,
+    pub eliminations: usize,
+    // By rule name, in the order of Sudoku::rules.
+    pub per_rule: Vec<(String, RuleStats)>,
+    // Always zero without std, there's no clock to read.
+    pub elapsed: Duration,
+}
+
+/// Pass counts of a solve, see Sudoku::solve_report().
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SolveReport {
+    pub passes: u32,
+    // Passes that placed nothing, the last one of a stuck solve
+    // included.
+    pub stalls: u32,
+    pub solved: u32,
+}
+
+impl Display for SolveStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        // rule                 calls  placed  elim
+        // exclusion_row         7290       0   342
+        writeln!(
+            f,
+            "{:<20} {:>8} {:>7} {:>6}",
+            "rule", "calls", "placed", "elim"
+        )?;
+        for (name, x) in &self.per_rule {
+            writeln!(
+                f,
+                "{:<20} {:>8} {:>7} {:>6}",
+                name, x.invocations, x.placements, x.eliminations
+            )?;
+        }
+        write!(
+            f,
+            "{} passes, {} placements, {} eliminations in {:?}",
+            self.passes, self.placements, self.eliminations, self.elapsed
+        )
+    }
+}
+
+// Collected pass by pass, see solve_with_stats().
+impl PassObserver for SolveStats {
+    fn considered(&mut self, rule: usize) {
+        self.per_rule[rule].1.invocations += 1;
+    }
+
+    fn passed(&mut self, sudoku: &Sudoku, step: &StepResult) {
+        for (k, rule) in sudoku.rules.iter().enumerate() {
+            let x = &mut self.per_rule[k].1;
+            for change in rule.last_changes() {
+                match change.kind {
+                    ChangeKind::Set(_) => x.placements += 1,
+                    ChangeKind::Eliminated(_) => x.eliminations += 1,
+                }
+            }
+        }
+        self.passes += 1;
+        self.placements += step.placed;
+    }
+}
+
+impl Sudoku {
+    /// Same as Solver::solve(), counting passes along the way.  Many
+    /// passes for few cells solved means the rules are thrashing.
+    pub fn solve_report(&mut self) -> SolveReport {
+        let mut report = SolveReport::default();
+        for step in self.passes() {
+            report.passes += 1;
+            report.stalls += (step.placed == 0) as u32;
+            report.solved += step.placed as u32;
+        }
+        report
+    }
+
+    /// Same as Solver::solve(), keeping count of what each rule did
+    /// along the way.
+    pub fn solve_with_stats(&mut self) -> SolveStats {
+        #[cfg(feature = "std")]
+        let start = std::time::Instant::now();
+
+        let mut stats = SolveStats {
+            per_rule: self
+                .rules
+                .iter()
+                .map(|x| (x.name().to_string(), RuleStats::default()))
+                .collect(),
+            ..Default::default()
+        };
+        self.passes_with(&mut stats).for_each(drop);
+        stats.eliminations = stats.per_rule.iter().map(|(_, x)| x.eliminations).sum();
+
+        #[cfg(feature = "std")]
+        {
+            stats.elapsed = start.elapsed();
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::presets;
+    use crate::structure::Solver;
+
+    #[test]
+    fn test_solve_with_stats() {
+        let mut s = presets::load_easy().with_standard_rules();
+        let stats = s.solve_with_stats();
+        assert!(s.is_solved());
+        assert_eq!(stats.placements, 43);
+        assert!(stats.passes > 0);
+
+        let names: Vec<&str> = s.rules.iter().map(|x| x.name()).collect();
+        let have: Vec<&str> = stats.per_rule.iter().map(|(x, _)| x.as_str()).collect();
+        assert_eq!(have, names);
+        for (name, x) in &stats.per_rule[..3] {
+            assert!(x.invocations > 0, "name={}", name);
+            assert!(x.eliminations > 0, "name={}", name);
+        }
+        let eliminations: usize = stats.per_rule.iter().map(|(_, x)| x.eliminations).sum();
+        assert_eq!(stats.eliminations, eliminations);
+
+        // Same board as a plain solve.
+        let mut want = presets::load_easy().with_standard_rules();
+        want.solve();
+        assert_eq!(s.cells, want.cells);
+    }
+
+    #[test]
+    fn test_solve_report() {
+        let mut s = presets::load_easy().with_standard_rules();
+        let report = s.solve_report();
+        let mut want = presets::load_easy().with_standard_rules();
+        assert_eq!(report.solved, want.solve() as u32);
+        assert!(report.passes >= 1);
+        assert_eq!(report.stalls, 0);
+        assert_eq!(s.cells, want.cells);
+
+        // Exclusion alone gets stuck on the expert board, the last
+        // pass showing it.
+        let mut s = presets::load_expert();
+        s.rules
+            .push(Box::new(crate::rules::ExclusionRule::new_row()));
+        let report = s.solve_report();
+        assert!(report.stalls >= 1);
+        assert!(!s.is_solved());
+        assert_eq!(
+            Sudoku::random_solved(1).solve_report(),
+            SolveReport::default()
+        );
+    }
+
+    #[test]
+    fn test_solve_stats_display() {
+        let mut s = presets::load_easy().with_standard_rules();
+        let table = s.solve_with_stats().to_string();
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 8);
+        assert!(lines[0].starts_with("rule"));
+        assert!(lines[1].starts_with("exclusion_row"));
+        assert!(lines[7].contains("43 placements"));
+    }
+}
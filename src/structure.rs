@@ -1,15 +1,84 @@
-use std::fmt::{Display, Error, Formatter};
-use std::str::FromStr;
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::convert::{TryFrom, TryInto};
+use core::fmt::{Debug, Display, Error, Formatter};
+use core::str::FromStr;
 
 // +--------+
 // | Traits |
 // +--------+
 
-pub trait Rule {
+/// What a rule did to a cell.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChangeKind {
+    // Value left as the only option.
+    Set(u8),
+    // Option shut.
+    Eliminated(u8),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Change {
+    pub index: usize,
+    pub kind: ChangeKind,
+}
+
+pub trait Rule: RuleClone {
+    // Called once before each pass over all targets.
+    fn begin_pass(&mut self) {}
     fn begin(&mut self) {}
+    // What the solver really calls before each target, for rules
+    // that need to know which one it is.
+    fn begin_at(&mut self, _target: usize) {
+        self.begin();
+    }
     fn predicate(&self, target: usize, origin: usize) -> bool;
     fn consider(&mut self, _target: &mut Cell, _other: Cell) {}
+    // What the solver really calls, for rules that need to know
+    // where the cells are.
+    fn consider_at(
+        &mut self,
+        _target_idx: usize,
+        target: &mut Cell,
+        _other_idx: usize,
+        other: &Cell,
+    ) {
+        self.consider(target, *other);
+    }
     fn end(&self, _target: &mut Cell) {}
+    // What the solver really calls after each target, for rules that
+    // need to know which one it is or keep track of what they did.
+    fn end_at(&mut self, _target_idx: usize, target: &mut Cell) {
+        self.end(target);
+    }
+
+    // Stable name of the kind of rule, e.g. "exclusion_row".  Rules
+    // without one can't be saved with the board or made by name.
+    fn name(&self) -> &str {
+        ""
+    }
+
+    // Changes made during the last pass, for rules that keep track.
+    fn last_changes(&self) -> &[Change] {
+        &[]
+    }
+}
+
+/// Boxed copy of a rule, used to duplicate a Sudoku.  Comes for free
+/// with Clone.
+pub trait RuleClone {
+    fn duplicate(&self) -> Box<dyn Rule>;
+}
+
+impl<T: Rule + Clone + 'static> RuleClone for T {
+    fn duplicate(&self) -> Box<dyn Rule> {
+        Box::new(self.clone())
+    }
 }
 
 pub trait Solver {
@@ -20,7 +89,7 @@ pub trait Solver {
 // | Cell |
 // +------+
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Cell {
     // Cell value
     value: u8,
@@ -30,6 +99,10 @@ pub struct Cell {
     //   8  7  6  5 4 3 2 1 0  # Bit position
     //   9  8  7  6 5 4 3 2 1  # Corresponding cell value
     options: u16,
+
+    // Number of values, 9 unless the cell belongs to a Grid of
+    // another size.
+    size: u8,
 }
 
 impl Cell {
@@ -40,22 +113,43 @@ impl Cell {
     }
 
     pub fn shut(&mut self, value: u8) {
-        self.options &= !Self::mask(value);
+        self.options &= !self.mask(value);
         self.invariant();
     }
 
     pub fn open(&mut self, value: u8) {
-        self.options |= Self::mask(value);
+        self.options |= self.mask(value);
         self.invariant();
     }
 
+    /// Shut the values outside lo..=hi, but never the last option:
+    /// bounds that leave none are a contradiction, which is left for
+    /// the rules to find.
+    pub fn shut_outside(&mut self, lo: u8, hi: u8) {
+        for v in 1..=self.size {
+            if !(lo..=hi).contains(&v) && self.is_open(v) && self.num_options() > 1 {
+                self.shut(v);
+            }
+        }
+    }
+
     pub fn single(&mut self, value: u8) {
         self.options = 0;
         self.open(value);
     }
 
     pub fn is_open(&self, value: u8) -> bool {
-        (self.options & Self::mask(value)) != 0
+        (self.options & self.mask(value)) != 0
+    }
+
+    /// Like is_open(), but returns None for values outside of 1-9
+    /// (1 to size() for cells of a Grid) instead of panicking.
+    pub fn try_is_open(&self, value: u8) -> Option<bool> {
+        if (1..=self.size).contains(&value) {
+            Some(self.is_open(value))
+        } else {
+            None
+        }
     }
 
     pub fn num_options(&self) -> u32 {
@@ -70,9 +164,42 @@ impl Cell {
         self.options
     }
 
-    fn mask(value: u8) -> u16 {
-        debug_assert!(1 <= value);
-        debug_assert!(value <= 9);
+    /// Same as options(), bit 0 stands for value 1.
+    pub fn options_mask(&self) -> u16 {
+        self.options
+    }
+
+    /// Unsolved cell with the given options, see mask_of().  Panics
+    /// on bits above the ninth.
+    pub fn from_options(mask: u16) -> Cell {
+        assert!(mask <= 0b1_1111_1111, "mask={:#x}", mask);
+        Cell {
+            value: 0,
+            options: mask,
+            size: 9,
+        }
+    }
+
+    /// Unsolved cell with values 1 to size open, for grids other than
+    /// 9x9.  Panics on sizes above 16.
+    pub fn with_size(size: u8) -> Cell {
+        assert!((1..=16).contains(&size), "size={}", size);
+        Cell {
+            value: 0,
+            options: ((1u32 << size) - 1) as u16,
+            size,
+        }
+    }
+
+    /// Number of values the cell can take.
+    pub fn size(&self) -> u8 {
+        self.size
+    }
+
+    fn mask(&self, value: u8) -> u16 {
+        // Checked in release too: an out of range value would
+        // otherwise silently produce a bogus mask.
+        assert!((1..=self.size).contains(&value), "value={}", value);
         1u16 << (value - 1)
     }
 
@@ -81,16 +208,14 @@ impl Cell {
         // Produces a better stack trace, but leaves code in
         // production.
         //
-        // if self.options == 0 {
-        //     debug_assert!(1 <= self.value);
+        // if self.value != 0 {
         //     debug_assert!(self.value <= 9);
-        // } else {
-        //     debug_assert!(self.value == 0);
+        //     debug_assert!(self.options == 0);
         // }
-        debug_assert!(
-            self.options == 0 && 1 <= self.value && self.value <= 9
-                || self.options != 0 && self.value == 0
-        );
+        //
+        // An unsolved cell without options is allowed, that's how a
+        // contradiction shows up while searching.
+        debug_assert!(self.value == 0 || self.value <= self.size && self.options == 0);
     }
 }
 
@@ -99,6 +224,30 @@ impl Default for Cell {
         Cell {
             value: 0,
             options: 0b1_1111_1111,
+            size: 9,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ParseCellError {
+    pub found: char,
+}
+
+impl TryFrom<char> for Cell {
+    type Error = ParseCellError;
+
+    // Digits 1-9 are solved cells, while '0', '.', '_' and ' ' mark
+    // an empty cell with all options open.
+    fn try_from(c: char) -> Result<Self, Self::Error> {
+        match c {
+            '1'..='9' => {
+                let mut cell = Cell::default();
+                cell.set(c as u8 - b'0');
+                Ok(cell)
+            }
+            '0' | '.' | '_' | ' ' => Ok(Cell::default()),
+            _ => Err(ParseCellError { found: c }),
         }
     }
 }
@@ -117,16 +266,114 @@ impl Solver for Cell {
     }
 }
 
+/// Bit of value in an options mask, 1 for 1 up to 256 for 9.
+pub fn mask_of(value: u8) -> u16 {
+    Cell::default().mask(value)
+}
+
+/// Values whose bits are set in mask, in increasing order.
+pub fn values_in(mask: u16) -> Vec<u8> {
+    (1u8..=9).filter(|v| mask & mask_of(*v) != 0).collect()
+}
+
 // +--------+
 // | Sudoku |
 // +--------+
 
+/// The 20 cells sharing a row, column or square with the given cell.
+pub fn peers(index: usize) -> [usize; 20] {
+    let (row, col) = (index / 9, index % 9);
+    let (top, left) = (row / 3 * 3, col / 3 * 3);
+    let mut xs = [0; 20];
+    let mut n = 0;
+    for i in 0..81 {
+        let (r, c) = (i / 9, i % 9);
+        let square = r / 3 * 3 == top && c / 3 * 3 == left;
+        if i != index && (r == row || c == col || square) {
+            xs[n] = i;
+            n += 1;
+        }
+    }
+    xs
+}
+
+// Parse a 1-based "r1c1" cell reference into a cell index.
+pub(crate) fn parse_cell(s: &str) -> Option<usize> {
+    let s = s.strip_prefix('r')?;
+    let mut parts = s.splitn(2, 'c');
+    let row: usize = parts.next()?.parse().ok()?;
+    let col: usize = parts.next()?.parse().ok()?;
+    if (1..=9).contains(&row) && (1..=9).contains(&col) {
+        Some((row - 1) * 9 + col - 1)
+    } else {
+        None
+    }
+}
+
+/// The kinds of units every Sudoku has, nine of each.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Unit {
+    Row,
+    Col,
+    Square,
+}
+
+impl Unit {
+    /// Index (0-8) of the unit of this kind holding the cell.
+    /// Squares are numbered in reading order.
+    pub fn of(&self, index: usize) -> usize {
+        match self {
+            Unit::Row => index / 9,
+            Unit::Col => index % 9,
+            Unit::Square => index / 27 * 3 + index % 9 / 3,
+        }
+    }
+
+    /// Both cells are in the same unit of this kind.
+    pub fn contains(&self, a: usize, b: usize) -> bool {
+        self.of(a) == self.of(b)
+    }
+
+    /// Cells of the unit with the given index, in reading order.
+    pub fn cells(&self, unit_index: usize) -> [usize; 9] {
+        let mut xs = [0; 9];
+        for (j, x) in xs.iter_mut().enumerate() {
+            *x = match self {
+                Unit::Row => unit_index * 9 + j,
+                Unit::Col => j * 9 + unit_index,
+                Unit::Square => (unit_index / 3 * 3 + j / 3) * 9 + unit_index % 3 * 3 + j % 3,
+            };
+        }
+        xs
+    }
+}
+
+// Cell indices of the 27 units: rows, then columns, then squares.
+fn units() -> [[usize; 9]; 27] {
+    let mut xs = [[0; 9]; 27];
+    for (k, x) in xs.iter_mut().enumerate() {
+        let unit = [Unit::Row, Unit::Col, Unit::Square][k / 9];
+        *x = unit.cells(k % 9);
+    }
+    xs
+}
+
 pub struct Sudoku {
     pub cells: [Cell; 81],
     pub rules: Vec<Box<dyn Rule>>,
+    // Cells solved by the puzzle itself rather than by solving.
+    pub givens: [bool; 81],
 }
 
 impl Sudoku {
+    /// Mark the currently solved cells as the givens.  Parsing does
+    /// this on its own.
+    pub fn mark_givens(&mut self) {
+        for i in 0..81 {
+            self.givens[i] = self.cells[i].value() != 0;
+        }
+    }
+
     pub fn is_solved(&self) -> bool {
         self.cells
             .iter()
@@ -135,234 +382,2237 @@ impl Sudoku {
             == self.cells.len()
     }
 
-    pub fn fork(&self) -> Vec<Sudoku> {
-        let min = self
-            .cells
-            .iter()
-            .enumerate()
-            .min_by_key(|(_, x)| x.num_options());
-        match min {
-            Some((_index, cell)) => {
-                let xs = vec![];
-                for value in 1u8..=9 {
-                    if cell.is_open(value) {
-                        // TODO: duplicate()
-                    }
-                }
-                xs
-            }
-            None => {
-                vec![]
-            }
-        }
+    /// Number of givens, cells solved by the puzzle itself.  Cells
+    /// the solver fills in don't count, see num_solved().
+    pub fn num_clues(&self) -> usize {
+        self.givens.iter().filter(|x| **x).count()
     }
-}
 
-impl Default for Sudoku {
-    fn default() -> Self {
-        Sudoku {
-            cells: [Default::default(); 81],
-            rules: vec![],
+    /// Number of solved cells, givens or not.
+    pub fn num_solved(&self) -> usize {
+        self.cells.iter().filter(|x| x.value() != 0).count()
+    }
+
+    /// A classic Sudoku with less than 17 clues can't have a unique
+    /// solution.
+    pub fn warn_if_underspecified(&self) -> Option<String> {
+        let n = self.num_clues();
+        if n < 17 {
+            Some(format!(
+                "only {} clues, at least 17 are needed for a unique solution",
+                n
+            ))
+        } else {
+            None
         }
     }
-}
 
-impl Display for Sudoku {
-    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+    /// Number of open options left over all cells, a rough measure of
+    /// the distance to a solution.
+    pub fn total_candidates(&self) -> u32 {
+        self.cells.iter().map(Cell::num_options).sum()
+    }
+
+    /// Render the grid like Display does, printing empty cells as
+    /// the given character.
+    pub fn format_grid(&self, empty: char) -> String {
         //  9 1 2 | 3 4 5 | 6 7 8
         //  3 4 5 | 6 7 8 | 9 1 2
         //  6 7 8 | 1 2 3 | 6 4 1
         // -------+-------+-------
+        let mut grid = String::new();
+
+        // Each row is collected first so that trailing blanks of
+        // empty cells can be trimmed.
+        let mut line = String::new();
         for (index, c) in self.cells.iter().enumerate() {
             if index != 0 {
                 if index % 9 == 0 {
-                    writeln!(f)?;
+                    grid.push_str(line.trim_end());
+                    grid.push('\n');
+                    line.clear();
                     if index % 27 == 0 {
-                        writeln!(f, "-------+-------+-------")?;
+                        grid.push_str("-------+-------+-------\n");
                     }
                 } else if index % 3 == 0 {
-                    write!(f, " |")?;
+                    line.push_str(" |");
                 }
             }
+            line.push(' ');
             if c.value != 0 {
-                write!(f, " {}", c.value)?;
+                line.push((b'0' + c.value) as char);
             } else {
-                write!(f, "  ")?;
+                line.push(empty);
             }
         }
-        Ok(())
+        grid.push_str(line.trim_end());
+        grid
     }
-}
 
-impl Solver for Sudoku {
-    fn solve(&mut self) -> u8 {
-        let mut sum = 0u8;
-        let mut iterations: u8 = 0;
-
-        while !self.is_solved() && iterations < 3 {
-            for target in 0..self.cells.len() {
-                // For each new target, begin() is called.
-                for rule in &mut self.rules {
-                    rule.begin();
-                }
-                for other in 0..self.cells.len() {
-                    let copy = self.cells[other];
-                    let mut x = &mut self.cells[target];
+    /// Compare only cell values, ignoring candidates of unsolved
+    /// cells.
+    pub fn same_values(&self, other: &Sudoku) -> bool {
+        self.cells
+            .iter()
+            .zip(other.cells.iter())
+            .all(|(a, b)| a.value() == b.value())
+    }
 
-                    for rule in &mut self.rules {
-                        if target != other && rule.predicate(target, other) {
-                            rule.consider(&mut x, copy);
-                        }
-                    }
+    /// Render the grid marking cells whose value or options differ
+    /// from other in brackets, empty cells as dots.
+    pub fn diff_string(&self, other: &Sudoku) -> String {
+        // [9] 1  2 | 3  4  5 | 6  7  8
+        // ---------+---------+---------
+        let mut lines = vec![];
+        for row in 0..9 {
+            if row != 0 && row % 3 == 0 {
+                lines.push("---------+---------+---------".to_string());
+            }
+            let mut line = String::new();
+            for col in 0..9 {
+                if col != 0 && col % 3 == 0 {
+                    line.push('|');
                 }
-                // For each target, end is called after successful
-                // iteration over all other cells.
-                for rule in &self.rules {
-                    rule.end(&mut self.cells[target]);
+                let (a, b) = (self.cells[row * 9 + col], other.cells[row * 9 + col]);
+                let v = match a.value() {
+                    0 => '.',
+                    v => (b'0' + v) as char,
+                };
+                if a == b {
+                    line.extend(&[' ', v, ' ']);
+                } else {
+                    line.extend(&['[', v, ']']);
                 }
             }
-
-            // Count how many cells are newly solved.  In case 0, we
-            // still want to continue as options might be updated.
-            let solved: u8 = self.cells.iter_mut().map(Cell::solve).sum();
-            sum += solved;
-            iterations += (solved <= 0) as u8;
+            lines.push(line.trim_end().to_string());
         }
-        sum
+        lines.join("\n")
     }
-}
 
-#[derive(Debug)]
-pub struct ParseSudokuError {}
+    /// Index of the unsolved cell with the fewest options, lowest
+    /// index first on ties, or None if every cell is solved.
+    pub fn most_constrained(&self) -> Option<usize> {
+        self.cells
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| x.value() == 0)
+            .min_by_key(|(_, x)| x.num_options())
+            .map(|(index, _)| index)
+    }
 
-impl FromStr for Sudoku {
-    type Err = ParseSudokuError;
+    /// How many cells of the unit are solved.
+    pub fn unit_filled_count(&self, unit: Unit, index: usize) -> usize {
+        unit.cells(index)
+            .iter()
+            .filter(|i| self.cells[**i].value() != 0)
+            .count()
+    }
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // Collect only digits out of the given input string.
-        let xs: Vec<u8> = s
-            .chars()
-            .map(|x| x.to_digit(10))
-            .filter(Option::is_some)
-            .map(|x| x.unwrap() as u8)
-            .collect();
+    /// Values not placed in the unit yet, in increasing order.
+    pub fn unit_missing_values(&self, unit: Unit, index: usize) -> Vec<u8> {
+        let cells = unit.cells(index);
+        (1u8..=9)
+            .filter(|v| cells.iter().all(|i| self.cells[*i].value() != *v))
+            .collect()
+    }
 
-        // Make sure we have the exact number of digits we need.
-        if xs.len() != 81 {
-            return Err(ParseSudokuError {});
+    /// Cell to branch on when searching: the fewest options like
+    /// most_constrained(), ties going to the cell with the most
+    /// unsolved peers, then to the lowest index.
+    pub fn select_branch_cell(&self) -> Option<usize> {
+        let degree = |i: usize| {
+            peers(i)
+                .iter()
+                .filter(|p| self.cells[**p].value() == 0)
+                .count()
+        };
+        let unsolved = (0..81).filter(|i| self.cells[*i].value() == 0);
+        let fewest = unsolved
+            .clone()
+            .map(|i| self.cells[i].num_options())
+            .min()?;
+        // Degrees only matter among the ties, skip the rest.
+        unsolved
+            .filter(|i| self.cells[*i].num_options() == fewest)
+            .min_by_key(|i| core::cmp::Reverse(degree(*i)))
+    }
+
+    /// No value appears twice in a row, column or square.
+    pub fn is_valid(&self) -> bool {
+        (0..81).all(|i| {
+            let v = self.cells[i].value();
+            v == 0 || peers(i).iter().all(|p| self.cells[*p].value() != v)
+        })
+    }
+
+    /// Every solved cell sharing its value with one of its peers, in
+    /// increasing order.
+    pub fn conflicts(&self) -> Vec<usize> {
+        (0..81)
+            .filter(|i| {
+                let v = self.cells[*i].value();
+                v != 0 && peers(*i).iter().any(|p| self.cells[*p].value() == v)
+            })
+            .collect()
+    }
+
+    /// Check the whole board hangs together: no value twice in a
+    /// unit, no options left on solved cells, values of solved cells
+    /// shut in all their peers and every unsolved cell with some
+    /// option left.  Reports the first problem found, checks in that
+    /// order, cells in reading order.
+    pub fn is_consistent(&self) -> Result<(), Inconsistency> {
+        let solved = (0..81).filter(|i| self.cells[*i].value() != 0);
+        for i in solved.clone() {
+            let v = self.cells[i].value();
+            if let Some(&p) = peers(i)
+                .iter()
+                .find(|p| **p > i && self.cells[**p].value() == v)
+            {
+                return Err(Inconsistency::Duplicate(i, p));
+            }
+        }
+        if let Some(i) = solved.clone().find(|i| self.cells[*i].options() != 0) {
+            return Err(Inconsistency::Lingering(i));
+        }
+        for i in solved {
+            let value = self.cells[i].value();
+            if let Some(&peer) = peers(i).iter().find(|p| self.cells[**p].is_open(value)) {
+                return Err(Inconsistency::NotExcluded {
+                    index: i,
+                    peer,
+                    value,
+                });
+            }
         }
+        match (0..81).find(|i| self.cells[*i].value() == 0 && self.cells[*i].num_options() == 0) {
+            Some(i) => Err(Inconsistency::Dead(i)),
+            None => Ok(()),
+        }
+    }
 
-        // Set each non-0 digit at the corresponding cell.
-        let mut s = Sudoku::default();
-        for (index, value) in xs.iter().enumerate().filter(|(_, x)| **x != 0) {
-            s.cells[index].set(*value);
+    /// Pairs of cells that are the only two places left for value in
+    /// some unit, lower index first, sorted and without repeats.
+    /// Units where value is already placed are skipped.
+    pub fn conjugate_pairs(&self, value: u8) -> Vec<(usize, usize)> {
+        let mut xs = vec![];
+        for unit in units().iter() {
+            if unit.iter().any(|i| self.cells[*i].value() == value) {
+                continue;
+            }
+            let open: Vec<usize> = unit
+                .iter()
+                .copied()
+                .filter(|i| self.cells[*i].is_open(value))
+                .collect();
+            if let [a, b] = open[..] {
+                xs.push((a.min(b), a.max(b)));
+            }
         }
-        Ok(s)
+        xs.sort_unstable();
+        xs.dedup();
+        xs
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::presets;
+    /// Rebuild the options of every unsolved cell from scratch: all
+    /// open, minus the values of its peers.  Returns how many of the
+    /// options open before got shut, or the first cell left without
+    /// any.
+    pub fn fix_candidates(&mut self) -> Result<usize, DeadCellError> {
+        let mut removed = 0;
+        for i in 0..81 {
+            if self.cells[i].value() != 0 {
+                continue;
+            }
+            let mut cell = Cell::default();
+            for p in peers(i).iter() {
+                let v = self.cells[*p].value();
+                if v != 0 {
+                    cell.shut(v);
+                }
+            }
+            removed += (self.cells[i].options() & !cell.options()).count_ones() as usize;
+            self.cells[i] = cell;
+        }
+        match (0..81).find(|i| self.cells[*i].value() == 0 && self.cells[*i].num_options() == 0) {
+            Some(index) => Err(DeadCellError { index }),
+            None => Ok(removed),
+        }
+    }
 
-    #[test]
-    fn test_cell_is_open() {
-        let c = Cell::default();
-        assert_eq!(c.num_options(), 9);
-        for i in 1..=9 {
-            assert!(c.is_open(i), "i={}", i);
+    /// First reason the board can't be solved any more: an unsolved
+    /// cell without options, in reading order, or else a value with
+    /// no place left in some unit, rows first, then columns, then
+    /// squares.  Values placed twice are for is_valid() to catch.
+    pub fn find_contradiction(&self) -> Option<Contradiction> {
+        if let Some(index) = (0..81).find(|i| {
+            let cell = &self.cells[*i];
+            cell.value() == 0 && cell.num_options() == 0
+        }) {
+            return Some(Contradiction::EmptyCell { index });
+        }
+        for &unit in &[Unit::Row, Unit::Col, Unit::Square] {
+            for unit_index in 0..9 {
+                let cells = unit.cells(unit_index);
+                for value in 1u8..=9 {
+                    let possible = cells.iter().any(|i| {
+                        let cell = &self.cells[*i];
+                        cell.value() == value || cell.is_open(value)
+                    });
+                    if !possible {
+                        return Some(Contradiction::MissingValueInUnit {
+                            unit,
+                            unit_index,
+                            value,
+                        });
+                    }
+                }
+            }
         }
+        None
     }
 
-    #[test]
-    #[should_panic]
-    fn test_cell_is_open_panic() {
-        // TODO: Why isn't there should_panic_debug()?  Tests in
-        // release will fail.
-        let c = Cell::default();
-        c.is_open(0);
+    // Some unsolved cell has no options left.
+    fn is_dead(&self) -> bool {
+        self.cells
+            .iter()
+            .any(|x| x.value() == 0 && x.num_options() == 0)
     }
 
-    #[test]
-    #[should_panic]
-    fn test_cell_is_open_panic2() {
-        let c = Cell::default();
-        c.is_open(10);
+    // Shut the value of a solved cell in all its peers.
+    fn exclude(&mut self, index: usize) {
+        let v = self.cells[index].value();
+        for p in peers(index).iter() {
+            self.cells[*p].shut(v);
+        }
     }
 
-    #[test]
-    fn test_cell_shut() {
-        let mut c = Cell::default();
-        assert!(c.options() == 0b1_1111_1111);
-        c.shut(7);
-        assert!(c.options() == 0b1_1011_1111);
-        c.shut(9);
-        assert!(c.options() == 0b0_1011_1111);
+    /// Copy of the board, rules included.
+    pub fn duplicate(&self) -> Sudoku {
+        Sudoku {
+            cells: self.cells,
+            rules: self.rules.iter().map(|x| x.duplicate()).collect(),
+            givens: self.givens,
+        }
     }
 
-    #[test]
-    fn test_cell_shut_open() {
-        let mut c = Cell::default();
-        assert!(c.is_open(1));
-        c.shut(1);
-        assert!(!c.is_open(1));
-        c.open(1);
-        assert!(c.is_open(1));
+    /// One duplicate for each option of the cell select_branch_cell()
+    /// picks, with that option set and shut in its peers.
+    pub fn fork(&self) -> Vec<Sudoku> {
+        match self.select_branch_cell() {
+            Some(index) => {
+                let cell = self.cells[index];
+                let mut xs = vec![];
+                for value in 1u8..=9 {
+                    if cell.is_open(value) {
+                        let mut x = self.duplicate();
+                        x.cells[index].set(value);
+                        x.exclude(index);
+                        xs.push(x);
+                    }
+                }
+                xs
+            }
+            None => {
+                vec![]
+            }
+        }
     }
 
-    #[test]
-    fn test_cell_solve() {
-        // Crate a cell.
-        let mut c = Cell::default();
-        assert_eq!(c.num_options(), 9);
-        assert_eq!(c.solve(), 0);
+    // Depth first search, solving with the rules at each step.
+    fn search(mut self) -> Option<Sudoku> {
+        self.solve();
+        if self.find_contradiction().is_some() || !self.is_valid() {
+            return None;
+        }
+        if self.is_solved() {
+            return Some(self);
+        }
+        self.fork().into_iter().find_map(Sudoku::search)
+    }
 
-        // Shut all options except for x.
-        let x = 7;
-        for i in 1..=9 {
-            if i != x {
-                c.shut(i);
+    /// Solved duplicate of the board, or None if there's no solution.
+    /// Rules are applied first and whatever they leave is searched.
+    pub fn solved(&self) -> Option<Sudoku> {
+        let mut s = self.duplicate();
+        for i in 0..81 {
+            if s.cells[i].value() != 0 {
+                s.exclude(i);
             }
         }
-        assert_eq!(c.num_options(), 1);
+        s.search()
+    }
 
-        // State before solve()ing.
-        assert_eq!(c.options, 0b0_0100_0000);
-        assert_eq!(c.value, 0);
+    // Like search(), counting solutions and giving up once there are
+    // limit of them.
+    fn count(mut self, limit: usize) -> usize {
+        self.solve();
+        if self.find_contradiction().is_some() || !self.is_valid() {
+            return 0;
+        }
+        if self.is_solved() {
+            return 1;
+        }
+        let mut n = 0;
+        for x in self.fork() {
+            n += x.count(limit - n);
+            if n >= limit {
+                break;
+            }
+        }
+        n
+    }
 
-        // Solve.
-        assert_eq!(c.solve(), 1);
+    /// Number of solutions, counting no further than limit.  Rules
+    /// are applied the same way solved() does.
+    pub fn count_solutions(&self, limit: usize) -> usize {
+        let mut s = self.duplicate();
+        for i in 0..81 {
+            if s.cells[i].value() != 0 {
+                s.exclude(i);
+            }
+        }
+        s.count(limit)
+    }
 
-        // State after solve()ing.
-        assert_eq!(c.options, 0b0_0000_0000);
-        assert_eq!(c.value, x);
+    pub fn has_unique_solution(&self) -> bool {
+        self.count_solutions(2) == 1
+    }
 
-        // Make sure calling solve() again returns 0 and does not
-        // alter the state.
-        assert_eq!(c.solve(), 0);
-        assert_eq!(c.options, 0b0_0000_0000);
-        assert_eq!(c.value, x);
+    /// Drop clues, one at a time in reading order, for as long as the
+    /// solution stays unique.  Every clue left is needed afterwards.
+    /// Options are rebuilt from the remaining values, rules are kept.
+    pub fn minimize(&mut self) {
+        let mut values = self.to_value_array();
+        let board = |values: [u8; 81], rules: &Sudoku| {
+            let mut s = Sudoku::try_from(values).unwrap();
+            s.rules = rules.rules.iter().map(|x| x.duplicate()).collect();
+            s
+        };
+        for i in 0..81 {
+            if values[i] == 0 {
+                continue;
+            }
+            let v = values[i];
+            values[i] = 0;
+            if !board(values, self).has_unique_solution() {
+                values[i] = v;
+            }
+        }
+        *self = board(values, self);
     }
 
-    #[test]
+    /// What goes in a single cell, worked out on a duplicate.  The
+    /// rules alone only narrow the cell down to its options; with
+    /// search each of those is kept only if some solution has it,
+    /// which takes at most one solution per option rather than all
+    /// of them.
+    pub fn solve_cell(&self, index: usize, search: bool) -> CellAnswer {
+        let mut s = self.duplicate();
+        for i in 0..81 {
+            if s.cells[i].value() != 0 {
+                s.exclude(i);
+            }
+        }
+        s.solve();
+        if s.is_dead() || !s.is_valid() {
+            return CellAnswer::Contradiction;
+        }
+        let cell = s.cells[index];
+        let mut candidates: Vec<u8> = if cell.value() != 0 {
+            vec![cell.value()]
+        } else {
+            (1u8..=9).filter(|v| cell.is_open(*v)).collect()
+        };
+        if search {
+            candidates.retain(|&v| {
+                let mut x = s.duplicate();
+                x.cells[index].set(v);
+                x.exclude(index);
+                x.search().is_some()
+            });
+        }
+        match candidates[..] {
+            [] => CellAnswer::Contradiction,
+            [v] => CellAnswer::Determined(v),
+            _ => CellAnswer::Ambiguous(candidates),
+        }
+    }
+}
+
+/// Why a board has no solution, see Sudoku::find_contradiction().
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Contradiction {
+    EmptyCell {
+        index: usize,
+    },
+    MissingValueInUnit {
+        unit: Unit,
+        unit_index: usize,
+        value: u8,
+    },
+}
+
+/// What Sudoku::is_consistent() found wrong.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Inconsistency {
+    // Two cells of a unit with the same value, lower index first.
+    Duplicate(usize, usize),
+    // Solved cell with open options.
+    Lingering(usize),
+    // Value of the solved cell at index still open in a peer.
+    NotExcluded {
+        index: usize,
+        peer: usize,
+        value: u8,
+    },
+    // Unsolved cell without any option.
+    Dead(usize),
+}
+
+/// Result of Sudoku::solve_cell().
+#[derive(Clone, Debug, PartialEq)]
+pub enum CellAnswer {
+    Determined(u8),
+    // Values still possible, in increasing order.
+    Ambiguous(Vec<u8>),
+    // The board can't be solved at all.
+    Contradiction,
+}
+
+impl Sudoku {
+    /// Place naked and hidden singles straight off the peer table and
+    /// the units, without going through the rules, until neither is
+    /// left.  Returns how many cells were placed.
+    pub fn propagate_singles(&mut self) -> u32 {
+        for i in 0..81 {
+            if self.cells[i].value() != 0 {
+                self.exclude(i);
+            }
+        }
+        let units = units();
+        let mut sum = 0;
+        loop {
+            let mut placed = 0;
+            for i in 0..81 {
+                if self.cells[i].solve() != 0 {
+                    self.exclude(i);
+                    placed += 1;
+                }
+            }
+            for unit in units.iter() {
+                for v in 1u8..=9 {
+                    let mut open = unit.iter().filter(|i| self.cells[**i].is_open(v));
+                    if let (Some(&i), None) = (open.next(), open.next()) {
+                        self.cells[i].set(v);
+                        self.exclude(i);
+                        placed += 1;
+                    }
+                }
+            }
+            if placed == 0 {
+                return sum;
+            }
+            sum += placed;
+        }
+    }
+
+    /// Place value in the cell at index, then shut it in the peers
+    /// and keep going from every peer that's left with a single
+    /// option, and from its peers in turn, without looking at the
+    /// rest of the board.  Meant for boards whose options are already
+    /// up to date, like after fix_candidates() or a solve.  The
+    /// contradiction, if any, is the first cell found broken, not
+    /// the lowest index.  Cells already placed aren't counted as
+    /// solved again.
+    ///
+    /// Only the peers of classic sudoku are excluded, self.rules
+    /// aren't run at all.  On boards with cages, thermometers,
+    /// inequalities or any other rules of their own, the result can
+    /// fall short of what solve() gets and even miss contradictions.
+    pub fn apply_and_propagate(&mut self, index: usize, value: u8) -> Progress {
+        self.propagate_from(index, value).0
+    }
+
+    // Does the work of apply_and_propagate(), also counting the
+    // distinct cells it looked at.
+    fn propagate_from(&mut self, index: usize, value: u8) -> (Progress, usize) {
+        let cell = &mut self.cells[index];
+        let fits = match cell.value() {
+            0 => cell.is_open(value),
+            v => v == value,
+        };
+        if !fits {
+            return (Progress::Contradiction { index }, 1);
+        }
+        let mut solved = (cell.value() == 0) as u32;
+        cell.set(value);
+
+        let mut touched = [false; 81];
+        touched[index] = true;
+        let mut queue = vec![index];
+        let mut progress = None;
+        'queue: while let Some(i) = queue.pop() {
+            let v = self.cells[i].value();
+            for &p in peers(i).iter() {
+                touched[p] = true;
+                let peer = &mut self.cells[p];
+                if peer.value() != 0 {
+                    if peer.value() == v {
+                        progress = Some(Progress::Contradiction { index: p });
+                        break 'queue;
+                    }
+                    continue;
+                }
+                peer.shut(v);
+                match peer.num_options() {
+                    0 => {
+                        progress = Some(Progress::Contradiction { index: p });
+                        break 'queue;
+                    }
+                    1 => {
+                        peer.solve();
+                        queue.push(p);
+                        solved += 1;
+                    }
+                    _ => (),
+                }
+            }
+        }
+        let progress = progress.unwrap_or(if self.is_solved() {
+            Progress::Solved
+        } else {
+            Progress::Stuck { solved }
+        });
+        (progress, touched.iter().filter(|x| **x).count())
+    }
+}
+
+impl Default for Sudoku {
+    fn default() -> Self {
+        Sudoku {
+            cells: [Default::default(); 81],
+            rules: vec![],
+            givens: [false; 81],
+        }
+    }
+}
+
+impl Display for Sudoku {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(f, "{}", self.format_grid(' '))
+    }
+}
+
+// Values on one line and rules by name, enough to tell failing
+// property test cases apart.
+impl Debug for Sudoku {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        let rules: Vec<&str> = self.rules.iter().map(|x| x.name()).collect();
+        f.debug_struct("Sudoku")
+            .field("cells", &self.to_line())
+            .field("rules", &rules)
+            .finish()
+    }
+}
+
+impl Sudoku {
+    /// One pass of every rule over every target, then newly single
+    /// cells are solved.  Returns how many were.
+    pub fn solve_step(&mut self) -> u8 {
+        self.step().placed as u8
+    }
+
+    /// One full pass, see solve_step(), reporting what changed.
+    pub fn step(&mut self) -> StepResult {
+        self.step_with(&mut ())
+    }
+
+    /// Same as step(), telling the observer about every cell a rule
+    /// considers and about the board once the pass is done.
+    pub fn step_with(&mut self, observer: &mut impl PassObserver) -> StepResult {
+        let step = step_cells(&mut self.rules, &mut self.cells, &mut |k| {
+            observer.considered(k)
+        });
+        observer.passed(self, &step);
+        step
+    }
+
+    /// Passes until the board is solved or a pass changes nothing.
+    /// This is the loop behind Solver::solve().
+    pub fn passes(&mut self) -> Passes<'_> {
+        self.passes_with(())
+    }
+
+    /// Same as passes(), each pass going through step_with().
+    pub fn passes_with<O: PassObserver>(&mut self, observer: O) -> Passes<'_, O> {
+        Passes {
+            sudoku: self,
+            observer,
+            done: false,
+        }
+    }
+
+    /// Solve like Solver::solve(), calling back with the board after
+    /// each pass.
+    pub fn solve_with_callback(&mut self, cb: impl FnMut(&Sudoku)) -> u8 {
+        self.passes_with(AfterPass(cb))
+            .map(|x| x.placed as u8)
+            .sum()
+    }
+
+    /// Run a single rule against a single target, the way a pass
+    /// would, and return how many options of the target changed.
+    /// Nothing gets placed, that's left to the next pass, and the
+    /// rule's last_changes() cover this call alone.
+    pub fn apply_rule_to(&mut self, rule_index: usize, target: usize) -> u32 {
+        let rules = &mut self.rules[rule_index..=rule_index];
+        rules[0].begin_pass();
+        let before = self.cells[target].options();
+        visit(rules, &mut self.cells, target, &mut |_| ());
+        (before ^ self.cells[target].options()).count_ones()
+    }
+}
+
+// One pass over any number of cells, shared with Grid: every rule
+// over every target, then newly single cells are solved.
+pub(crate) fn step_cells(
+    rules: &mut [Box<dyn Rule>],
+    cells: &mut [Cell],
+    considered: &mut impl FnMut(usize),
+) -> StepResult {
+    let before = cells.to_vec();
+    let candidates: u32 = cells.iter().map(Cell::num_options).sum();
+    visit_all(rules, cells, considered);
+    let placed: usize = cells.iter_mut().map(|x| x.solve() as usize).sum();
+    let shut = candidates - cells.iter().map(Cell::num_options).sum::<u32>();
+    StepResult {
+        placed,
+        eliminated: shut.saturating_sub(placed as u32) as usize,
+        changed: (0..cells.len())
+            .filter(|i| cells[*i] != before[*i])
+            .collect(),
+    }
+}
+
+// A pass without placing anything: begin_pass(), then every cell as
+// the target.
+fn visit_all(rules: &mut [Box<dyn Rule>], cells: &mut [Cell], considered: &mut impl FnMut(usize)) {
+    for rule in rules.iter_mut() {
+        rule.begin_pass();
+    }
+    for target in 0..cells.len() {
+        visit(rules, cells, target, considered);
+    }
+}
+
+// One target of a pass: begin_at(), consider_at() with every other
+// cell the rule's predicate picks, then end_at(), calling back with
+// the position of the rule in rules whenever it considers a cell.
+fn visit(
+    rules: &mut [Box<dyn Rule>],
+    cells: &mut [Cell],
+    target: usize,
+    considered: &mut impl FnMut(usize),
+) {
+    for rule in rules.iter_mut() {
+        rule.begin_at(target);
+    }
+    for other in 0..cells.len() {
+        let copy = cells[other];
+        let x = &mut cells[target];
+        for (k, rule) in rules.iter_mut().enumerate() {
+            if target != other && rule.predicate(target, other) {
+                rule.consider_at(target, x, other, &copy);
+                considered(k);
+            }
+        }
+    }
+    for rule in rules.iter_mut() {
+        rule.end_at(target, &mut cells[target]);
+    }
+}
+
+/// Watches passes, see Sudoku::step_with().  () watches nothing.
+pub trait PassObserver {
+    // The rule at this position of rules considered a cell.
+    fn considered(&mut self, _rule: usize) {}
+
+    // A pass is done, here is the board after it.
+    fn passed(&mut self, _sudoku: &Sudoku, _step: &StepResult) {}
+}
+
+impl PassObserver for () {}
+
+impl<O: PassObserver> PassObserver for &mut O {
+    fn considered(&mut self, rule: usize) {
+        (**self).considered(rule);
+    }
+
+    fn passed(&mut self, sudoku: &Sudoku, step: &StepResult) {
+        (**self).passed(sudoku, step);
+    }
+}
+
+// Calls back with the board after each pass.
+struct AfterPass<F>(F);
+
+impl<F: FnMut(&Sudoku)> PassObserver for AfterPass<F> {
+    fn passed(&mut self, sudoku: &Sudoku, _: &StepResult) {
+        (self.0)(sudoku);
+    }
+}
+
+/// What a single pass changed.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StepResult {
+    // Cells solved.
+    pub placed: usize,
+    // Options shut, not counting the last option of solved cells.
+    pub eliminated: usize,
+    // Cells whose value or options changed, in increasing order.
+    pub changed: Vec<usize>,
+}
+
+/// Iterator over the passes of a solve, see Sudoku::passes().
+pub struct Passes<'a, O = ()> {
+    sudoku: &'a mut Sudoku,
+    observer: O,
+    done: bool,
+}
+
+impl<O: PassObserver> Iterator for Passes<'_, O> {
+    type Item = StepResult;
+
+    fn next(&mut self) -> Option<StepResult> {
+        if self.done || self.sudoku.is_solved() {
+            return None;
+        }
+        // A pass without any change is reported too, it's the one
+        // that shows the rules are stuck.
+        let x = self.sudoku.step_with(&mut self.observer);
+        self.done = x.changed.is_empty();
+        Some(x)
+    }
+}
+
+impl Solver for Sudoku {
+    fn solve(&mut self) -> u8 {
+        self.passes().map(|x| x.placed as u8).sum()
+    }
+}
+
+/// How a solve ended, see Sudoku::solve2().
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Progress {
+    Solved,
+    // Rules ran out of moves after solving this many cells.
+    Stuck { solved: u32 },
+    // Lowest index of a cell with no options left or sharing its
+    // value with a peer.
+    Contradiction { index: usize },
+}
+
+impl Sudoku {
+    /// Same as Solver::solve(), telling a solved board from a stuck
+    /// or broken one.
+    pub fn solve2(&mut self) -> Progress {
+        let solved: u32 = self.passes().map(|x| x.placed as u32).sum();
+        let dead = (0..81).find(|i| {
+            let cell = &self.cells[*i];
+            cell.value() == 0 && cell.num_options() == 0
+        });
+        let conflict = self.conflicts().first().copied();
+        match (dead, conflict) {
+            (Some(a), Some(b)) => Progress::Contradiction { index: a.min(b) },
+            (Some(index), None) | (None, Some(index)) => Progress::Contradiction { index },
+            (None, None) if self.is_solved() => Progress::Solved,
+            (None, None) => Progress::Stuck { solved },
+        }
+    }
+
+    /// Same as solve2(), with the order of work pinned down: every
+    /// pass visits targets 0 to 80, for each target the other cells
+    /// 0 to 80, and for each pair the rules in the order of
+    /// Sudoku::rules.  Cells only get placed once a pass is over.
+    /// Nothing depends on hashing, the clock or the platform, so the
+    /// same board with the same rules always ends up the same.
+    pub fn solve_deterministic(&mut self) -> Progress {
+        self.solve2()
+    }
+
+    /// The 81 values in reading order, 0 for unsolved cells.
+    pub fn to_line(&self) -> String {
+        self.cells
+            .iter()
+            .map(|x| (b'0' + x.value()) as char)
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseSudokuError {}
+
+/// Unsolved cell whose peers hold all nine values.
+#[derive(Debug, PartialEq)]
+pub struct DeadCellError {
+    pub index: usize,
+}
+
+impl FromStr for Sudoku {
+    type Err = ParseSudokuError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Collect only cell characters out of the given input string.
+        // Whitespace separates cells, everything else (like the
+        // borders Display produces) is ignored.
+        let xs: Vec<Cell> = s
+            .chars()
+            .filter(|x| !x.is_whitespace())
+            .filter_map(|x| Cell::try_from(x).ok())
+            .collect();
+
+        // Make sure we have the exact number of cells we need.
+        if xs.len() != 81 {
+            return Err(ParseSudokuError {});
+        }
+
+        let mut s = Sudoku::default();
+        s.cells.copy_from_slice(&xs);
+        s.mark_givens();
+        Ok(s)
+    }
+}
+
+impl TryFrom<&str> for Sudoku {
+    type Error = ParseSudokuError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl Sudoku {
+    /// Same as parse(), but unsolved cells start with the options
+    /// their peers leave rather than all of them, see
+    /// fix_candidates().  A cell left without any still parses,
+    /// solve2() reports it.
+    pub fn parse_fixed(s: &str) -> Result<Sudoku, ParseSudokuError> {
+        let mut sudoku: Sudoku = s.parse()?;
+        let _ = sudoku.fix_candidates();
+        Ok(sudoku)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ValuesError {
+    // Slice that isn't 81 values long, by its length.
+    Length(usize),
+    // First value outside of 0-9, by index.
+    Value(usize),
+}
+
+impl TryFrom<[u8; 81]> for Sudoku {
+    type Error = ValuesError;
+
+    // Values in reading order, 0 for empty cells.
+    fn try_from(values: [u8; 81]) -> Result<Self, Self::Error> {
+        if let Some(i) = values.iter().position(|v| *v > 9) {
+            return Err(ValuesError::Value(i));
+        }
+        let mut s = Sudoku::default();
+        for (cell, &v) in s.cells.iter_mut().zip(values.iter()) {
+            if v != 0 {
+                cell.set(v);
+            }
+        }
+        s.mark_givens();
+        Ok(s)
+    }
+}
+
+impl Sudoku {
+    /// Same as TryFrom<[u8; 81]>, for slices of any length.
+    pub fn try_from_slice(values: &[u8]) -> Result<Sudoku, ValuesError> {
+        let values: [u8; 81] = values
+            .try_into()
+            .map_err(|_| ValuesError::Length(values.len()))?;
+        Sudoku::try_from(values)
+    }
+
+    /// Values in reading order, 0 for unsolved cells.
+    pub fn to_value_array(&self) -> [u8; 81] {
+        let mut values = [0; 81];
+        for (v, cell) in values.iter_mut().zip(self.cells.iter()) {
+            *v = cell.value();
+        }
+        values
+    }
+
+    /// Values row by row, 0 for unsolved cells.
+    pub fn to_grid(&self) -> [[u8; 9]; 9] {
+        let mut grid = [[0; 9]; 9];
+        for (i, cell) in self.cells.iter().enumerate() {
+            grid[i / 9][i % 9] = cell.value();
+        }
+        grid
+    }
+
+    /// Board out of what to_grid() produces, 0 for empty cells.
+    pub fn from_grid(grid: [[u8; 9]; 9]) -> Result<Sudoku, ParseSudokuError> {
+        let mut values = [0; 81];
+        for (i, v) in values.iter_mut().enumerate() {
+            *v = grid[i / 9][i % 9];
+        }
+        Sudoku::try_from(values).map_err(|_| ParseSudokuError {})
+    }
+
+    /// Options in reading order, bit 0 for value 1.  Solved cells
+    /// have none.
+    pub fn to_options_array(&self) -> [u16; 81] {
+        let mut options = [0; 81];
+        for (x, cell) in options.iter_mut().zip(self.cells.iter()) {
+            *x = cell.options();
+        }
+        options
+    }
+}
+
+impl Sudoku {
+    /// Nine rows of nine comma-separated values, empty cells as empty
+    /// fields.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::new();
+        for row in self.cells.chunks(9) {
+            let fields: Vec<String> = row
+                .iter()
+                .map(|x| match x.value() {
+                    0 => String::new(),
+                    v => v.to_string(),
+                })
+                .collect();
+            csv.push_str(&fields.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+
+    /// Parse what to_csv() produces.  Fields may be quoted and padded
+    /// with whitespace, and empty fields or 0 mean empty cells.
+    pub fn from_csv(s: &str) -> Result<Sudoku, ParseSudokuError> {
+        let rows: Vec<&str> = s.lines().filter(|x| !x.trim().is_empty()).collect();
+        if rows.len() != 9 {
+            return Err(ParseSudokuError {});
+        }
+        let mut sudoku = Sudoku::default();
+        for (r, row) in rows.iter().enumerate() {
+            let fields: Vec<&str> = row.split(',').collect();
+            if fields.len() != 9 {
+                return Err(ParseSudokuError {});
+            }
+            for (c, field) in fields.iter().enumerate() {
+                let field = field.trim();
+                let field = field
+                    .strip_prefix('"')
+                    .and_then(|x| x.strip_suffix('"'))
+                    .unwrap_or(field)
+                    .trim();
+                match field {
+                    "" | "0" => (),
+                    _ => match field.parse::<u8>() {
+                        Ok(v) if (1..=9).contains(&v) => sudoku.cells[r * 9 + c].set(v),
+                        _ => return Err(ParseSudokuError {}),
+                    },
+                }
+            }
+        }
+        sudoku.mark_givens();
+        Ok(sudoku)
+    }
+}
+
+impl Sudoku {
+    /// Pencil marks of the whole board, each cell drawn as three rows
+    /// of three with every open value in its place, solved cells as
+    /// "[v]" in the middle.  Double lines separate the squares:
+    ///
+    /// ```text
+    /// ╔═══╤═══╤═══╦═══╤ ...
+    /// ║ 2 │   │  3║   │
+    /// ║   │[4]│   ║   │
+    /// ║78 │   │  9║   │
+    /// ╟───┼───┼───╫───┼ ...
+    /// ```
+    pub fn to_pencil_grid(&self) -> String {
+        // Border line out of the left, cell, thin and thick pieces.
+        let border = |l: char, fill: char, thin: char, thick: char, r: char| {
+            let mut line = String::new();
+            line.push(l);
+            for c in 0..9 {
+                (0..3).for_each(|_| line.push(fill));
+                line.push(match c {
+                    8 => r,
+                    2 | 5 => thick,
+                    _ => thin,
+                });
+            }
+            line
+        };
+        let mut lines = vec![border('╔', '═', '╤', '╦', '╗')];
+        for row in 0..9 {
+            for sub in 0..3u8 {
+                let mut line = String::from("║");
+                for col in 0..9 {
+                    let cell = self.cells[row * 9 + col];
+                    if cell.value() != 0 && sub == 1 {
+                        line.push('[');
+                        line.push((b'0' + cell.value()) as char);
+                        line.push(']');
+                    } else if cell.value() != 0 {
+                        line.push_str("   ");
+                    } else {
+                        for v in sub * 3 + 1..=sub * 3 + 3 {
+                            line.push(if cell.is_open(v) {
+                                (b'0' + v) as char
+                            } else {
+                                ' '
+                            });
+                        }
+                    }
+                    line.push(if col % 3 == 2 { '║' } else { '│' });
+                }
+                lines.push(line);
+            }
+            lines.push(match row {
+                8 => border('╚', '═', '╧', '╩', '╝'),
+                2 | 5 => border('╠', '═', '╪', '╬', '╣'),
+                _ => border('╟', '─', '┼', '╫', '╢'),
+            });
+        }
+        let mut s = lines.join("\n");
+        s.push('\n');
+        s
+    }
+
+    /// Candidates of every cell, one line each in reading order:
+    /// "r1c1: 2589" lists the options of an unsolved cell, "r1c1: =3"
+    /// gives the value of a solved one and "r1c1: -" marks a cell
+    /// without any options left.
+    pub fn candidates_string(&self) -> String {
+        let mut s = String::new();
+        for (i, x) in self.cells.iter().enumerate() {
+            s.push_str(&format!("r{}c{}: ", i / 9 + 1, i % 9 + 1));
+            if x.value() != 0 {
+                s.push('=');
+                s.push((b'0' + x.value()) as char);
+            } else if x.num_options() == 0 {
+                s.push('-');
+            } else {
+                s.extend(
+                    (1u8..=9)
+                        .filter(|v| x.is_open(*v))
+                        .map(|v| (b'0' + v) as char),
+                );
+            }
+            s.push('\n');
+        }
+        s
+    }
+
+    /// Parse what candidates_string() produces.  Lines may come in
+    /// any order, but every cell has to be listed exactly once.
+    pub fn from_candidates_string(s: &str) -> Result<Sudoku, ParseSudokuError> {
+        let mut sudoku = Sudoku::default();
+        let mut seen = [false; 81];
+        for line in s.lines().map(str::trim).filter(|x| !x.is_empty()) {
+            let mut parts = line.splitn(2, ':');
+            let index = parse_cell(parts.next().unwrap()).ok_or(ParseSudokuError {})?;
+            let field = parts.next().ok_or(ParseSudokuError {})?.trim();
+            if seen[index] {
+                return Err(ParseSudokuError {});
+            }
+            seen[index] = true;
+
+            let cell = &mut sudoku.cells[index];
+            let digit = |c: char| c.to_digit(10).filter(|v| *v != 0).map(|v| v as u8);
+            if let Some(rest) = field.strip_prefix('=') {
+                let mut chars = rest.chars();
+                match (chars.next().and_then(digit), chars.next()) {
+                    (Some(v), None) => cell.set(v),
+                    _ => return Err(ParseSudokuError {}),
+                }
+            } else if field == "-" {
+                for v in 1u8..=9 {
+                    cell.shut(v);
+                }
+            } else {
+                let mut mask = 0u16;
+                for c in field.chars() {
+                    let v = digit(c).ok_or(ParseSudokuError {})?;
+                    mask |= mask_of(v);
+                }
+                if mask == 0 {
+                    return Err(ParseSudokuError {});
+                }
+                for v in (1u8..=9).filter(|v| mask & mask_of(*v) == 0) {
+                    cell.shut(v);
+                }
+            }
+        }
+        if seen.iter().all(|x| *x) {
+            Ok(sudoku)
+        } else {
+            Err(ParseSudokuError {})
+        }
+    }
+
+    /// Parse the grids of Simple Sudoku files, borders of `*`, `-`,
+    /// `+` and `|` included.  Either the givens, `.` or `0` for empty
+    /// cells:
+    ///
+    /// ```text
+    /// *-----------*
+    /// |34.|...|.7.|
+    /// ...
+    /// ```
+    ///
+    /// or 81 groups of pencil marks separated by whitespace, which
+    /// become the options of their cells.  A cell with a single
+    /// candidate is left unsolved for solve() to settle, and with no
+    /// way to tell givens apart, none are marked.
+    pub fn from_simple_sudoku(s: &str) -> Result<Sudoku, ParseSudokuError> {
+        let tokens: Vec<&str> = s
+            .lines()
+            .filter(|x| !x.chars().all(|c| c.is_whitespace() || "*-+|=".contains(c)))
+            .flat_map(|x| x.split(|c: char| c == '|' || c.is_whitespace()))
+            .filter(|x| !x.is_empty())
+            .collect();
+        let digit = |c: char| c.to_digit(10).filter(|v| *v != 0).map(|v| v as u8);
+
+        let mut sudoku = Sudoku::default();
+        if tokens.len() == 81 && tokens.iter().any(|x| x.len() > 1) {
+            for (cell, token) in sudoku.cells.iter_mut().zip(tokens.iter()) {
+                let mut mask = 0u16;
+                for c in token.chars() {
+                    mask |= mask_of(digit(c).ok_or(ParseSudokuError {})?);
+                }
+                *cell = Cell::from_options(mask);
+            }
+            return Ok(sudoku);
+        }
+
+        let chars: Vec<char> = tokens.iter().flat_map(|x| x.chars()).collect();
+        if chars.len() != 81 {
+            return Err(ParseSudokuError {});
+        }
+        for (cell, &c) in sudoku.cells.iter_mut().zip(chars.iter()) {
+            match c {
+                '.' | '0' => (),
+                _ => cell.set(digit(c).ok_or(ParseSudokuError {})?),
+            }
+        }
+        sudoku.mark_givens();
+        Ok(sudoku)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::presets;
+    use crate::rules::ExclusionRule;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_cell_is_open() {
+        let c = Cell::default();
+        assert_eq!(c.num_options(), 9);
+        for i in 1..=9 {
+            assert!(c.is_open(i), "i={}", i);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_cell_is_open_panic() {
+        let c = Cell::default();
+        c.is_open(0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_cell_is_open_panic2() {
+        let c = Cell::default();
+        c.is_open(10);
+    }
+
+    #[test]
+    fn test_cell_try_is_open() {
+        let mut c = Cell::default();
+        c.shut(4);
+        assert_eq!(c.try_is_open(0), None);
+        assert_eq!(c.try_is_open(10), None);
+        assert_eq!(c.try_is_open(4), Some(false));
+        assert_eq!(c.try_is_open(9), Some(true));
+    }
+
+    #[test]
+    fn test_cell_with_size() {
+        let mut c = Cell::with_size(16);
+        assert_eq!(c.num_options(), 16);
+        assert_eq!(c.try_is_open(16), Some(true));
+        assert_eq!(c.try_is_open(17), None);
+        c.shut(12);
+        assert!(!c.is_open(12));
+        assert_eq!(Cell::with_size(4).try_is_open(5), None);
+        c.set(16);
+        assert_eq!(c.value(), 16);
+    }
+
+    #[test]
+    fn test_cell_shut() {
+        let mut c = Cell::default();
+        assert!(c.options() == 0b1_1111_1111);
+        c.shut(7);
+        assert!(c.options() == 0b1_1011_1111);
+        c.shut(9);
+        assert!(c.options() == 0b0_1011_1111);
+    }
+
+    #[test]
+    fn test_cell_shut_open() {
+        let mut c = Cell::default();
+        assert!(c.is_open(1));
+        c.shut(1);
+        assert!(!c.is_open(1));
+        c.open(1);
+        assert!(c.is_open(1));
+    }
+
+    #[test]
+    fn test_cell_shut_outside() {
+        let mut c = Cell::default();
+        c.shut_outside(3, 5);
+        assert_eq!(c.options(), 0b0_0001_1100);
+        c.shut_outside(7, 9);
+        // The last option stays open.
+        assert_eq!(c.options(), 0b0_0001_0000);
+    }
+
+    #[test]
+    fn test_cell_solve() {
+        // Crate a cell.
+        let mut c = Cell::default();
+        assert_eq!(c.num_options(), 9);
+        assert_eq!(c.solve(), 0);
+
+        // Shut all options except for x.
+        let x = 7;
+        for i in 1..=9 {
+            if i != x {
+                c.shut(i);
+            }
+        }
+        assert_eq!(c.num_options(), 1);
+
+        // State before solve()ing.
+        assert_eq!(c.options, 0b0_0100_0000);
+        assert_eq!(c.value, 0);
+
+        // Solve.
+        assert_eq!(c.solve(), 1);
+
+        // State after solve()ing.
+        assert_eq!(c.options, 0b0_0000_0000);
+        assert_eq!(c.value, x);
+
+        // Make sure calling solve() again returns 0 and does not
+        // alter the state.
+        assert_eq!(c.solve(), 0);
+        assert_eq!(c.options, 0b0_0000_0000);
+        assert_eq!(c.value, x);
+    }
+
+    #[test]
+    fn test_cell_try_from_digit() {
+        let c = Cell::try_from('7').unwrap();
+        assert_eq!(c.value(), 7);
+        assert_eq!(c.options(), 0);
+    }
+
+    #[test]
+    fn test_cell_try_from_empty() {
+        for x in &['.', '0', '_', ' '] {
+            let c = Cell::try_from(*x).unwrap();
+            assert_eq!(c, Cell::default(), "x={:?}", x);
+        }
+    }
+
+    #[test]
+    fn test_cell_try_from_invalid() {
+        assert_eq!(Cell::try_from('x'), Err(ParseCellError { found: 'x' }));
+        assert_eq!(Cell::try_from('|'), Err(ParseCellError { found: '|' }));
+    }
+
+    #[test]
     fn test_sudoku_display() {
         let have = presets::load_easy().to_string();
         let want = "
- 3 4   |       |   7  
- 8     | 4   7 | 2 5  
- 7   6 | 8     | 3   9
+ 3 4   |       |   7
+ 8     | 4   7 | 2 5
+ 7   6 | 8     | 3   9
+-------+-------+-------
+   1 3 |     6 | 4
+     7 |     4 |   1
+     4 |       | 6   3
+-------+-------+-------
+   7 9 | 6 5   | 1   2
+       | 7     | 5 9 8
+   3   | 2 9 1 | 7"
+            .strip_prefix('\n')
+            .unwrap();
+        assert_eq!(have, want);
+        assert!(have.lines().all(|x| x == x.trim_end()));
+    }
+
+    #[test]
+    fn test_sudoku_total_candidates() {
+        assert_eq!(Sudoku::default().total_candidates(), 729);
+
+        let mut s = presets::load_easy();
+        let before = s.total_candidates();
+        assert_eq!(before, 43 * 9);
+        s.rules.push(Box::new(ExclusionRule::new_row()));
+        s.solve();
+        assert!(s.total_candidates() < before);
+    }
+
+    #[test]
+    fn test_sudoku_most_constrained() {
+        let mut s = presets::load_easy();
+        assert_eq!(s.most_constrained(), Some(2));
+
+        for v in 1..=7 {
+            s.cells[40].shut(v);
+        }
+        s.cells[50].shut(1);
+        assert_eq!(s.cells[40].num_options(), 2);
+        assert_eq!(s.most_constrained(), Some(40));
+
+        // Ties go to the lowest index.
+        for v in 1..=7 {
+            s.cells[30].shut(v);
+        }
+        assert_eq!(s.most_constrained(), Some(30));
+
+        let mut s = Sudoku::default();
+        for x in s.cells.iter_mut() {
+            x.set(1);
+        }
+        assert_eq!(s.most_constrained(), None);
+    }
+
+    #[test]
+    fn test_sudoku_num_clues() {
+        assert_eq!(presets::load_easy().num_clues(), 38);
+        assert_eq!(presets::load_hard2().warn_if_underspecified(), None);
+
+        let mut s = Sudoku::default();
+        for i in 0..16 {
+            s.cells[i * 5].set(i as u8 % 9 + 1);
+        }
+        assert_eq!(s.num_clues(), 0);
+        s.mark_givens();
+        assert_eq!(s.num_clues(), 16);
+        assert!(s.warn_if_underspecified().unwrap().contains("16"));
+
+        // Solving adds solved cells, not clues.
+        let mut s = presets::load_easy().with_standard_rules();
+        s.solve();
+        assert_eq!(s.num_clues(), 38);
+        assert_eq!(s.num_solved(), 81);
+    }
+
+    #[test]
+    fn test_peers() {
+        let xs = peers(0);
+        assert_eq!(&xs[..8], &[1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(&xs[8..12], &[9, 10, 11, 18]);
+        assert_eq!(xs[19], 72);
+        for i in 0..81 {
+            assert!(!peers(i).contains(&i));
+        }
+    }
+
+    #[test]
+    fn test_sudoku_conflicts() {
+        let mut s = presets::load_easy();
+        assert!(s.conflicts().is_empty());
+
+        // Cell 9 holds an 8, put another one further down column 0.
+        s.cells[36].set(8);
+        assert_eq!(s.conflicts(), vec![9, 36]);
+        assert!(!s.is_valid());
+    }
+
+    #[test]
+    fn test_cell_from_options() {
+        let cell = Cell::from_options(mask_of(7));
+        assert_eq!(cell.value(), 0);
+        assert_eq!(cell.num_options(), 1);
+        assert!(cell.is_open(7) && !cell.is_open(6));
+        assert_eq!(values_in(cell.options_mask()), vec![7]);
+
+        let mut cell = Cell::default();
+        cell.shut(2);
+        cell.shut(9);
+        assert_eq!(values_in(cell.options_mask()), vec![1, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(Cell::from_options(cell.options_mask()), cell);
+        assert!(values_in(0).is_empty());
+        assert_eq!(mask_of(1) | mask_of(9), 0b1_0000_0001);
+    }
+
+    #[test]
+    fn test_unit() {
+        assert!(Unit::Square.contains(0, 10));
+        assert!(!Unit::Square.contains(2, 3));
+        assert!(!Unit::Row.contains(0, 9));
+        assert!(Unit::Col.contains(0, 9));
+        assert_eq!(Unit::Row.cells(1), [9, 10, 11, 12, 13, 14, 15, 16, 17]);
+        assert_eq!(Unit::Col.cells(8), [8, 17, 26, 35, 44, 53, 62, 71, 80]);
+        assert_eq!(Unit::Square.cells(4), [30, 31, 32, 39, 40, 41, 48, 49, 50]);
+        for unit in [Unit::Row, Unit::Col, Unit::Square].iter() {
+            for i in 0..9 {
+                assert!(unit.cells(i).iter().all(|x| unit.of(*x) == i));
+            }
+        }
+    }
+
+    #[test]
+    fn test_sudoku_conjugate_pairs() {
+        let mut s = Sudoku::default();
+        assert!(s.conjugate_pairs(5).is_empty());
+
+        // Row 0 keeps 5 in cells 1 and 7 only.
+        for i in (0..9).filter(|i| *i != 1 && *i != 7) {
+            s.cells[i].shut(5);
+        }
+        // Cells 30 and 31 pair up in both row 3 and the center square,
+        // and are only listed once.
+        for i in (27..36).chain([39, 40, 41, 48, 49, 50].iter().copied()) {
+            if i != 30 && i != 31 {
+                s.cells[i].shut(5);
+            }
+        }
+        // Bottom right square keeps 5 in cells 60 and 80 only.
+        for i in [61, 62, 69, 70, 71, 78, 79].iter() {
+            s.cells[*i].shut(5);
+        }
+        assert_eq!(s.conjugate_pairs(5), vec![(1, 7), (30, 31), (60, 80)]);
+        assert!(s.conjugate_pairs(4).is_empty());
+
+        // Once 5 is placed in row 0, its leftover candidate doesn't
+        // count.
+        s.cells[1].set(5);
+        assert_eq!(s.conjugate_pairs(5), vec![(30, 31), (60, 80)]);
+    }
+
+    #[test]
+    fn test_sudoku_propagate_singles() {
+        let mut want = presets::load_easy().with_standard_rules();
+        want.solve();
+        let mut s = presets::load_easy();
+        assert_eq!(s.propagate_singles(), 43);
+        assert_eq!(s.cells, want.cells);
+
+        // Stuck boards end up in the same state too.
+        let mut want = presets::load_expert().with_standard_rules();
+        want.solve();
+        let mut s = presets::load_expert();
+        s.propagate_singles();
+        assert!(!s.is_solved());
+        assert_eq!(s.cells, want.cells);
+    }
+
+    #[test]
+    fn test_sudoku_apply_and_propagate() {
+        // On an empty board nothing goes past the peers, while a pass
+        // of the rules visits every cell.
+        let mut want = Sudoku::default().with_standard_rules();
+        want.cells[40].set(5);
+        want.solve();
+        let mut have = Sudoku::default();
+        let (progress, touched) = have.propagate_from(40, 5);
+        assert_eq!(progress, Progress::Stuck { solved: 1 });
+        assert_eq!(touched, 21);
+        assert_eq!(have.cells, want.cells);
+        // Placed already, nothing new gets solved.
+        assert_eq!(
+            have.apply_and_propagate(40, 5),
+            Progress::Stuck { solved: 0 }
+        );
+        assert_eq!(have.cells, want.cells);
+
+        // The easy solution with the top left four cells of the first
+        // square emptied, placing one settles the others.
+        let solution = {
+            let mut s = presets::load_easy().with_standard_rules();
+            s.solve();
+            s.cells
+        };
+        let mut s = Sudoku {
+            cells: solution,
+            ..Default::default()
+        };
+        for &i in [0, 1, 9, 10].iter() {
+            s.cells[i] = Cell::default();
+        }
+        assert!(s.fix_candidates().is_ok());
+
+        let mut want = s.duplicate().with_standard_rules();
+        want.cells[0].set(solution[0].value());
+        want.solve();
+        let mut have = s.duplicate();
+        let (progress, touched) = have.propagate_from(0, solution[0].value());
+        assert_eq!(progress, Progress::Solved);
+        assert_eq!(have.cells, want.cells);
+        assert_eq!(have.cells, solution);
+        // The four cells and their peers: the first two rows and
+        // columns, and the rest of the first square.
+        let near = |i: usize| i / 9 < 2 || i % 9 < 2 || i / 9 < 3 && i % 9 < 3;
+        assert_eq!(touched, (0..81).filter(|i| near(*i)).count());
+
+        // Values that don't fit.
+        let v = solution[1].value();
+        assert_eq!(
+            s.duplicate().apply_and_propagate(0, v),
+            Progress::Contradiction { index: 0 }
+        );
+        assert_eq!(
+            s.duplicate().apply_and_propagate(2, v),
+            Progress::Contradiction { index: 2 }
+        );
+    }
+
+    #[test]
+    fn test_sudoku_apply_rule_to() {
+        // r1 of the easy board holds 3, 4 and 7.
+        let mut s = presets::load_easy();
+        s.rules.push(Box::new(ExclusionRule::new_row()));
+        assert_eq!(s.apply_rule_to(0, 2), 3);
+        assert_eq!(s.cells[2].options(), 0b1_1011_0011);
+        assert_eq!(s.apply_rule_to(0, 2), 0);
+        // The rest of the row is untouched.
+        assert_eq!(s.cells[3].num_options(), 9);
+        // Solved targets have no options to lose.
+        assert_eq!(s.apply_rule_to(0, 0), 0);
+        assert!(s.rules[0].last_changes().is_empty());
+
+        // Changes are those of the last call only.
+        let mut s = presets::load_easy();
+        s.rules.push(Box::new(ExclusionRule::new_row()));
+        s.apply_rule_to(0, 2);
+        assert_eq!(s.rules[0].last_changes().len(), 3);
+        s.apply_rule_to(0, 5);
+        assert_eq!(s.rules[0].last_changes().len(), 3);
+        assert!(s.rules[0].last_changes().iter().all(|x| x.index == 5));
+    }
+
+    #[test]
+    fn test_sudoku_unit_values() {
+        let mut s = Sudoku::default();
+        s.cells[21].set(4);
+        s.cells[26].set(8);
+        assert_eq!(s.unit_filled_count(Unit::Row, 2), 2);
+        assert_eq!(
+            s.unit_missing_values(Unit::Row, 2),
+            vec![1, 2, 3, 5, 6, 7, 9]
+        );
+        assert_eq!(s.unit_filled_count(Unit::Col, 3), 1);
+        assert_eq!(s.unit_filled_count(Unit::Square, 0), 0);
+        assert_eq!(s.unit_missing_values(Unit::Square, 0).len(), 9);
+
+        let s = Sudoku::random_solved(1);
+        assert_eq!(s.unit_filled_count(Unit::Square, 8), 9);
+        assert!(s.unit_missing_values(Unit::Square, 8).is_empty());
+    }
+
+    #[test]
+    fn test_sudoku_select_branch_cell() {
+        // r1c1 and r9c9 both have two options, but r1c1 lost a peer
+        // to the 5 next to it.
+        let mut s = Sudoku::default();
+        s.cells[1].set(5);
+        for &i in &[0, 80] {
+            for v in 3u8..=9 {
+                s.cells[i].shut(v);
+            }
+        }
+        assert_eq!(s.most_constrained(), Some(0));
+        assert_eq!(s.select_branch_cell(), Some(80));
+        assert_eq!(s.fork().len(), 2);
+        assert_eq!(s.fork()[0].cells[80].value(), 1);
+
+        // Full ties still go to the lowest index.
+        let mut s = Sudoku::default();
+        assert_eq!(s.select_branch_cell(), Some(0));
+        s.cells[40].shut(1);
+        assert_eq!(s.select_branch_cell(), Some(40));
+        assert_eq!(Sudoku::random_solved(1).select_branch_cell(), None);
+    }
+
+    #[test]
+    fn test_sudoku_find_contradiction() {
+        let mut s = presets::load_hard().with_standard_rules();
+        assert_eq!(s.find_contradiction(), None);
+        s.solve();
+        assert_eq!(s.find_contradiction(), None);
+
+        let mut s = Sudoku::default();
+        s.cells[30] = Cell::from_options(0);
+        s.cells[40] = Cell::from_options(0);
+        assert_eq!(
+            s.find_contradiction(),
+            Some(Contradiction::EmptyCell { index: 30 })
+        );
+
+        // No 4 anywhere in the third column.
+        let mut s = Sudoku::default();
+        for i in Unit::Col.cells(2).iter() {
+            s.cells[*i].shut(4);
+        }
+        let want = Contradiction::MissingValueInUnit {
+            unit: Unit::Col,
+            unit_index: 2,
+            value: 4,
+        };
+        assert_eq!(s.find_contradiction(), Some(want));
+        assert!(s.solved().is_none());
+
+        // A placed 4 is fine.
+        s.cells[2].set(4);
+        assert_eq!(s.find_contradiction(), None);
+    }
+
+    #[test]
+    fn test_sudoku_is_consistent() {
+        let mut s = presets::load_hard();
+        assert!(s.is_consistent().is_err());
+        s.propagate_singles();
+        assert_eq!(s.is_consistent(), Ok(()));
+
+        let mut s = presets::load_hard();
+        s.fix_candidates().unwrap();
+        assert_eq!(s.is_consistent(), Ok(()));
+        s.cells[0].open(7);
+        let want = Inconsistency::NotExcluded {
+            index: 2,
+            peer: 0,
+            value: 7,
+        };
+        assert_eq!(s.is_consistent(), Err(want));
+
+        let mut s = Sudoku::default();
+        s.cells[76].set(3);
+        s.cells[4].set(3);
+        assert_eq!(s.is_consistent(), Err(Inconsistency::Duplicate(4, 76)));
+
+        let mut s = Sudoku::default();
+        s.cells[10] = Cell {
+            value: 0,
+            options: 0,
+            size: 9,
+        };
+        assert_eq!(s.is_consistent(), Err(Inconsistency::Dead(10)));
+
+        // Only a release build lets set() and open() get this far.
+        let mut s = Sudoku::default();
+        s.cells[10] = Cell {
+            value: 5,
+            options: 1,
+            size: 9,
+        };
+        assert_eq!(s.is_consistent(), Err(Inconsistency::Lingering(10)));
+    }
+
+    #[test]
+    fn test_sudoku_fix_candidates() {
+        let mut s = presets::load_hard();
+        assert_eq!(s.cells[0].num_options(), 9);
+        assert_eq!(s.fix_candidates(), Ok(291));
+        assert_eq!(s.cells[0].options(), 0b0_1000_0101);
+        assert_eq!(s.cells[1].options(), 0b1_1010_0101);
+        assert_eq!(s.cells[40].options(), 0b1_0011_1100);
+        assert_eq!(s.cells[2].value(), 7);
+        assert_eq!(s.fix_candidates(), Ok(0));
+
+        // Stale marks on a single cell, too few or too many.
+        s.cells[0] = Cell::default();
+        s.cells[1].shut(1);
+        assert_eq!(s.fix_candidates(), Ok(6));
+        assert_eq!(s.cells[0].options(), 0b0_1000_0101);
+        assert_eq!(s.cells[1].options(), 0b1_1010_0101);
+
+        let mut s = Sudoku::default();
+        for v in 1u8..=8 {
+            s.cells[v as usize].set(v);
+        }
+        s.cells[9].set(9);
+        assert_eq!(s.fix_candidates(), Err(DeadCellError { index: 0 }));
+    }
+
+    #[test]
+    fn test_sudoku_parse_fixed() {
+        let mut want = presets::load_hard();
+        want.fix_candidates().unwrap();
+        let s = Sudoku::parse_fixed(&presets::load_hard().to_line()).unwrap();
+        assert_eq!(s.cells, want.cells);
+        assert_eq!(s.givens, want.givens);
+        assert!(Sudoku::parse_fixed("123").is_err());
+    }
+
+    #[test]
+    fn test_sudoku_solve_deterministic() {
+        let solve = |mut s: Sudoku| {
+            let progress = s.solve_deterministic();
+            (progress, s.to_line(), s.candidates_string())
+        };
+        for s in [presets::load_hard(), presets::load_expert()].iter() {
+            let a = solve(s.duplicate().with_standard_rules());
+            let b = solve(s.duplicate().with_standard_rules());
+            assert_eq!(a, b);
+        }
+
+        // Exclusion and single options reach the same fixpoint
+        // whatever order they run in.
+        let mut reversed = presets::load_hard().with_standard_rules();
+        reversed.rules.reverse();
+        assert_eq!(reversed.solve_deterministic(), Progress::Solved);
+        let (_, want, _) = solve(presets::load_hard().with_standard_rules());
+        assert_eq!(reversed.to_line(), want);
+        assert!(!want.contains('0'));
+    }
+
+    // Rule id, target, other and value of other.
+    type Visit = (usize, usize, usize, u8);
+
+    // Logs every pair it considers, every cell relating to every
+    // other.
+    #[derive(Clone)]
+    struct OrderRule {
+        id: usize,
+        log: Rc<RefCell<Vec<Visit>>>,
+    }
+
+    impl Rule for OrderRule {
+        fn predicate(&self, _: usize, _: usize) -> bool {
+            true
+        }
+
+        fn consider_at(&mut self, target_idx: usize, _: &mut Cell, other_idx: usize, other: &Cell) {
+            let x = (self.id, target_idx, other_idx, other.value());
+            self.log.borrow_mut().push(x);
+        }
+    }
+
+    #[test]
+    fn test_sudoku_solve_deterministic_order() {
+        // The standard rules leave 12 singles in the first pass ahead
+        // of the logging rules, but those only get placed after it.
+        let log = Rc::new(RefCell::new(vec![]));
+        let mut s = presets::load_easy().with_standard_rules();
+        let before = s.to_value_array();
+        for id in 0..2 {
+            s.rules.push(Box::new(OrderRule {
+                id,
+                log: log.clone(),
+            }));
+        }
+        // Duplicates log to the same place.
+        assert_eq!(s.duplicate().step().placed, 12);
+        let passes = s.duplicate().passes().count();
+        log.borrow_mut().clear();
+        s.solve_deterministic();
+
+        let mut want = vec![];
+        for target in 0..81 {
+            for other in (0..81).filter(|x| *x != target) {
+                for id in 0..2 {
+                    want.push((id, target, other, before[other]));
+                }
+            }
+        }
+        let log = log.borrow();
+        assert_eq!(log.len(), want.len() * passes);
+        assert_eq!(log[..want.len()], want[..]);
+    }
+
+    #[test]
+    fn test_sudoku_solve2() {
+        let mut s = presets::load_easy().with_standard_rules();
+        assert_eq!(s.solve2(), Progress::Solved);
+        assert_eq!(s.solve2(), Progress::Solved);
+
+        let mut s = presets::load_expert();
+        s.rules.push(Box::new(ExclusionRule::new_row()));
+        s.rules.push(Box::new(ExclusionRule::new_col()));
+        s.rules.push(Box::new(ExclusionRule::new_square()));
+        let clues = s.num_solved() as u32;
+        match s.solve2() {
+            Progress::Stuck { solved } => {
+                assert_eq!(solved + clues, s.num_solved() as u32);
+                assert!(!s.is_solved());
+            }
+            x => panic!("{:?}", x),
+        }
+
+        // Two 3s in the top row.
+        let mut s = presets::load_easy().with_standard_rules();
+        s.cells[2].set(3);
+        assert_eq!(s.solve2(), Progress::Contradiction { index: 0 });
+
+        let mut s = presets::load_easy().with_standard_rules();
+        s.cells[2] = Cell::default();
+        for v in 1u8..=9 {
+            s.cells[2].shut(v);
+        }
+        assert_eq!(s.solve2(), Progress::Contradiction { index: 2 });
+    }
+
+    #[test]
+    fn test_sudoku_passes() {
+        let mut s = presets::load_easy().with_standard_rules();
+        let passes: Vec<StepResult> = s.passes().collect();
+        assert!(s.is_solved());
+        assert_eq!(passes.len(), 4);
+        assert_eq!(passes.iter().map(|x| x.placed).sum::<usize>(), 43);
+        assert!(passes.iter().all(|x| !x.changed.is_empty()));
+
+        // Nothing left to do on a solved board.
+        assert_eq!(s.step(), StepResult::default());
+        assert_eq!(s.passes().count(), 0);
+
+        // Stuck boards end with a pass that changes nothing.
+        let mut s = presets::load_expert().with_standard_rules();
+        let last = s.passes().last().unwrap();
+        assert!(!s.is_solved());
+        assert_eq!(last, StepResult::default());
+    }
+
+    #[test]
+    fn test_sudoku_solve_with_callback() {
+        let passes = presets::load_easy().with_standard_rules().passes().count();
+        let mut s = presets::load_easy().with_standard_rules();
+        let mut calls = 0;
+        let mut clues = vec![];
+        assert_eq!(
+            s.solve_with_callback(|x| {
+                calls += 1;
+                clues.push(x.num_solved());
+            }),
+            43
+        );
+        assert_eq!(calls, passes);
+        assert_eq!(clues.last(), Some(&81));
+        assert!(clues.windows(2).all(|x| x[0] < x[1]));
+    }
+
+    // Remembers every pair of cells it's asked to consider, shared
+    // with the test through the Rc.
+    #[derive(Clone)]
+    struct RecordRule {
+        pairs: Rc<RefCell<Vec<(usize, usize)>>>,
+    }
+
+    impl Rule for RecordRule {
+        fn name(&self) -> &str {
+            "record"
+        }
+
+        fn predicate(&self, target: usize, other: usize) -> bool {
+            Unit::Square.contains(target, other)
+        }
+
+        fn consider_at(&mut self, target_idx: usize, _: &mut Cell, other_idx: usize, _: &Cell) {
+            self.pairs.borrow_mut().push((target_idx, other_idx));
+        }
+    }
+
+    #[test]
+    fn test_sudoku_consider_at() {
+        let pairs = Rc::new(RefCell::new(vec![]));
+        let mut s = Sudoku::default();
+        s.rules.push(Box::new(RecordRule {
+            pairs: pairs.clone(),
+        }));
+        s.solve_step();
+
+        let pairs = pairs.borrow();
+        assert_eq!(pairs.len(), 81 * 8);
+        let have: Vec<usize> = pairs
+            .iter()
+            .filter(|(t, _)| *t == 40)
+            .map(|(_, o)| *o)
+            .collect();
+        let want: Vec<usize> = peers(40)
+            .iter()
+            .copied()
+            .filter(|o| Unit::Square.contains(40, *o))
+            .collect();
+        assert_eq!(have, want);
+    }
+
+    #[test]
+    fn test_sudoku_step() {
+        let mut s = Sudoku::default().with_standard_rules();
+        s.cells[0].set(5);
+        let x = s.step();
+        // The 5 is shut in all 20 peers, nothing gets solved.
+        assert_eq!(x.placed, 0);
+        assert_eq!(x.eliminated, 20);
+        assert_eq!(x.changed, peers(0).to_vec());
+    }
+
+    #[test]
+    fn test_sudoku_solved() {
+        let s = presets::load_expert();
+        let before = s.to_string();
+        let solved = s.solved().unwrap();
+        assert!(solved.is_solved());
+        assert!(solved.is_valid());
+        assert_eq!(s.to_string(), before);
+        assert_eq!(s.num_clues(), 23);
+        for i in 0..81 {
+            let v = s.cells[i].value();
+            assert!(v == 0 || solved.cells[i].value() == v);
+        }
+    }
+
+    #[test]
+    fn test_sudoku_solved_rules() {
+        let s = presets::load_hard().with_standard_rules();
+        let solved = s.solved().unwrap();
+        assert!(solved.is_solved());
+        assert!(solved.is_valid());
+        assert_eq!(solved.rules.len(), 6);
+        assert_eq!(s.num_clues(), 25);
+    }
+
+    #[test]
+    fn test_sudoku_solved_none() {
+        let mut s = Sudoku::default();
+        s.cells[0].set(1);
+        for (i, v) in [(3, 2), (4, 3), (5, 4), (6, 5), (7, 6), (8, 7)].iter() {
+            s.cells[*i].set(*v);
+        }
+        // Cells 1 and 2 need 8 and 9, which are already taken in
+        // their square.
+        s.cells[19].set(8);
+        s.cells[20].set(9);
+        assert!(s.solved().is_none());
+    }
+
+    #[test]
+    fn test_sudoku_format_grid() {
+        let have = presets::load_easy().format_grid('.');
+        let want = "
+ 3 4 . | . . . | . 7 .
+ 8 . . | 4 . 7 | 2 5 .
+ 7 . 6 | 8 . . | 3 . 9
 -------+-------+-------
-   1 3 |     6 | 4    
-     7 |     4 |   1  
-     4 |       | 6   3
+ . 1 3 | . . 6 | 4 . .
+ . . 7 | . . 4 | . 1 .
+ . . 4 | . . . | 6 . 3
 -------+-------+-------
-   7 9 | 6 5   | 1   2
-       | 7     | 5 9 8
-   3   | 2 9 1 | 7    "
+ . 7 9 | 6 5 . | 1 . 2
+ . . . | 7 . . | 5 9 8
+ . 3 . | 2 9 1 | 7 . ."
             .strip_prefix('\n')
             .unwrap();
         assert_eq!(have, want);
+
+        // Dotted output parses back to the same grid.
+        let s: Sudoku = have.parse().unwrap();
+        assert_eq!(s.cells, presets::load_easy().cells);
+    }
+
+    #[test]
+    fn test_sudoku_diff_string() {
+        let s = presets::load_easy();
+        let have = s.diff_string(&s);
+        assert!(!have.contains('['));
+        assert_eq!(have.lines().count(), 11);
+        assert_eq!(have.lines().next(), Some(" 3  4  . | .  .  . | .  7  ."));
+
+        let mut next = s.duplicate();
+        next.cells[2].set(5);
+        let have = next.diff_string(&s);
+        assert_eq!(have.matches('[').count(), 1);
+        assert_eq!(have.lines().next(), Some(" 3  4 [5]| .  .  . | .  7  ."));
+        assert_eq!(
+            s.diff_string(&next).lines().next(),
+            Some(" 3  4 [.]| .  .  . | .  7  .")
+        );
+    }
+
+    #[test]
+    fn test_sudoku_pencil_grid() {
+        let mut s = presets::load_easy();
+        s.fix_candidates().unwrap();
+        let grid = s.to_pencil_grid();
+        let lines: Vec<&str> = grid.lines().collect();
+        // A border, then three lines and a border for each row.
+        assert_eq!(lines.len(), 37);
+        assert!(lines.iter().all(|x| x.chars().count() == 37));
+        assert!(lines[0].starts_with("╔═══╤═══╤═══╦"));
+        assert!(lines[4].starts_with("╟───┼───┼───╫"));
+        assert!(lines[12].starts_with("╠═══╪═══╪═══╬"));
+        assert!(lines[36].ends_with("╧═══╧═══╝"));
+
+        // r1c1 holds a 3 and r1c3 is down to 1, 2 and 5.
+        assert!(lines[2].starts_with("║[3]│[4]│ 5 ║"));
+        let r1c3 = |k: usize| lines[1 + k].chars().skip(9).take(3).collect::<String>();
+        assert_eq!(r1c3(0), "12 ");
+        assert_eq!(r1c3(1), " 5 ");
+        assert_eq!(r1c3(2), "   ");
+    }
+
+    #[test]
+    fn test_sudoku_candidates_string() {
+        let mut s = presets::load_easy().with_standard_rules();
+        s.solve_step();
+        s.cells[72].shut(4);
+        let have = s.candidates_string();
+        let lines: Vec<&str> = have.lines().collect();
+        assert_eq!(lines.len(), 81);
+        assert_eq!(lines[0], "r1c1: =3");
+        assert_eq!(lines[2], "r1c3: 125");
+        assert_eq!(lines[72], "r9c1: 56");
+
+        let back = Sudoku::from_candidates_string(&have).unwrap();
+        assert_eq!(back.cells, s.cells);
+        // Order doesn't matter.
+        let reversed: Vec<&str> = lines.iter().rev().copied().collect();
+        let back = Sudoku::from_candidates_string(&reversed.join("\n")).unwrap();
+        assert_eq!(back.cells, s.cells);
+
+        // A single stray elimination shows up in its own line.
+        let mut stray = s.duplicate();
+        stray.cells[2].shut(2);
+        let snapshot = stray.candidates_string();
+        let changed: Vec<&str> = have
+            .lines()
+            .zip(snapshot.lines())
+            .filter(|(a, b)| a != b)
+            .map(|(_, b)| b)
+            .collect();
+        assert_eq!(changed, vec!["r1c3: 15"]);
+
+        let mut dead = Sudoku::default();
+        for v in 1..=9 {
+            dead.cells[40].shut(v);
+        }
+        let text = dead.candidates_string();
+        assert!(text.contains("r5c5: -\n"));
+        assert_eq!(
+            Sudoku::from_candidates_string(&text).unwrap().cells,
+            dead.cells
+        );
+    }
+
+    #[test]
+    fn test_sudoku_candidates_string_invalid() {
+        let text = Sudoku::default().candidates_string();
+        assert!(Sudoku::from_candidates_string(&text[..text.len() - 20]).is_err());
+        let twice = text.replace("r9c9: 123456789", "r9c8: 1");
+        assert!(Sudoku::from_candidates_string(&twice).is_err());
+        for bad in ["r1c1: =0", "r1c1: =12", "r1c1: 10", "r1c1: ", "r0c1: 1"].iter() {
+            let text = text.replace("r1c1: 123456789", bad);
+            assert!(
+                Sudoku::from_candidates_string(&text).is_err(),
+                "bad={}",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn test_sudoku_csv() {
+        let csv = presets::load_easy().to_csv();
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("3,4,,,,,,7,"));
+        assert_eq!(lines.next(), Some("8,,,4,,7,2,5,"));
+        assert_eq!(csv.lines().count(), 9);
+
+        let s = Sudoku::from_csv(&csv).unwrap();
+        assert_eq!(s.cells, presets::load_easy().cells);
+    }
+
+    #[test]
+    fn test_sudoku_csv_quoted() {
+        let mut csv = String::from(" \"3\" , \"4\",\"\", 0,,,, 7 ,\n");
+        for _ in 0..8 {
+            csv.push_str(",,,,,,,,\n");
+        }
+        let s = Sudoku::from_csv(&csv).unwrap();
+        assert_eq!(s.cells[0].value(), 3);
+        assert_eq!(s.cells[1].value(), 4);
+        assert_eq!(s.cells[2].value(), 0);
+        assert_eq!(s.cells[3].value(), 0);
+        assert_eq!(s.cells[7].value(), 7);
+        assert_eq!(s.num_clues(), 3);
+
+        assert!(Sudoku::from_csv("1,2,3").is_err());
+        assert!(Sudoku::from_csv(&csv.replace("7", "x")).is_err());
+        assert!(Sudoku::from_csv(&csv.replace("7", "10")).is_err());
+    }
+
+    #[test]
+    fn test_sudoku_from_simple_sudoku() {
+        let line = presets::load_easy().to_line().replace('0', ".");
+        let mut givens = String::from("*-----------*\n");
+        for (r, row) in line.as_bytes().chunks(9).enumerate() {
+            let row = core::str::from_utf8(row).unwrap();
+            givens.push_str(&format!("|{}|{}|{}|\n", &row[..3], &row[3..6], &row[6..]));
+            givens.push_str(if r == 8 {
+                "*-----------*\n"
+            } else if r % 3 == 2 {
+                "|---+---+---|\n"
+            } else {
+                ""
+            });
+        }
+        let s = Sudoku::from_simple_sudoku(&givens).unwrap();
+        assert_eq!(s.cells, presets::load_easy().cells);
+        assert_eq!(s.givens, presets::load_easy().givens);
+
+        // Pencil marks, r1c1 down to {3, 7} and r1c2 to {5}.
+        let mut marks = String::from("*---*\n");
+        for r in 0..9 {
+            let row: Vec<&str> = (0..9)
+                .map(|c| match (r, c) {
+                    (0, 0) => "37",
+                    (0, 1) => "5",
+                    _ => "123456789",
+                })
+                .collect();
+            marks.push_str(&format!("| {} |\n", row.join(" ")));
+        }
+        marks.push_str("*---*\n");
+        let mut s = Sudoku::from_simple_sudoku(&marks).unwrap();
+        assert_eq!(s.cells[0].options(), 0b0_0100_0100);
+        assert_eq!(s.cells[0].value(), 0);
+        assert_eq!(s.cells[1].options(), 0b0_0001_0000);
+        assert_eq!(s.cells[1].value(), 0);
+        assert_eq!(s.cells[2].num_options(), 9);
+        assert_eq!(s.num_clues(), 0);
+        s.solve();
+        assert_eq!(s.cells[1].value(), 5);
+
+        assert!(Sudoku::from_simple_sudoku(&givens.replace('3', "x")).is_err());
+        assert!(Sudoku::from_simple_sudoku(&marks.replace("37", "07")).is_err());
+        assert!(Sudoku::from_simple_sudoku("|12.|").is_err());
     }
 
     #[test]
@@ -381,6 +2631,161 @@ mod tests {
         assert_eq!(s.cells[0].value, 9);
     }
 
+    #[test]
+    fn test_sudoku_same_values() {
+        let a = presets::load_easy();
+        let mut b = presets::load_easy();
+        b.cells[2].shut(1);
+        assert!(a.same_values(&b));
+        assert!(a.cells != b.cells);
+
+        b.cells[2].set(1);
+        assert!(!a.same_values(&b));
+    }
+
+    #[test]
+    fn test_sudoku_parse_dotted() {
+        let s: Sudoku = "
+..7.....5
+5..42...1
+.4...56..
+6.51.....
+.....8...
+2......8.
+92..7..5.
+.73..6...
+..1..9..2"
+            .parse()
+            .unwrap();
+        assert_eq!(s.cells, presets::load_hard().cells);
+    }
+
+    #[test]
+    fn test_sudoku_try_from_str() {
+        let dotted = "
+..7.....5
+5..42...1
+.4...56..
+6.51.....
+.....8...
+2......8.
+92..7..5.
+.73..6...
+..1..9..2";
+        let s = Sudoku::try_from(dotted).unwrap();
+        assert_eq!(s.cells, presets::load_hard().cells);
+        assert!(Sudoku::try_from("..7").is_err());
+    }
+
+    #[test]
+    fn test_sudoku_count_solutions() {
+        let s = presets::load_easy().with_standard_rules();
+        assert_eq!(s.count_solutions(5), 1);
+        assert!(s.has_unique_solution());
+        let s = Sudoku::default().with_standard_rules();
+        assert_eq!(s.count_solutions(5), 5);
+        assert!(!s.has_unique_solution());
+
+        let mut broken = presets::load_easy();
+        broken.cells[2].set(3);
+        assert_eq!(broken.count_solutions(5), 0);
+    }
+
+    #[test]
+    fn test_sudoku_minimize() {
+        let mut s = presets::load_easy().with_standard_rules();
+        let before = s.num_clues();
+        s.minimize();
+        assert!(s.num_clues() < before);
+        assert_eq!(s.rules.len(), 6);
+        assert!(s.has_unique_solution());
+        assert!(s
+            .solved()
+            .unwrap()
+            .same_values(&presets::load_easy().solved().unwrap()));
+
+        // Every clue left is needed.
+        let values = s.to_value_array();
+        for i in (0..81).filter(|i| values[*i] != 0) {
+            let mut fewer = values;
+            fewer[i] = 0;
+            let x = Sudoku::try_from(fewer).unwrap().with_standard_rules();
+            assert!(!x.has_unique_solution(), "i={}", i);
+        }
+    }
+
+    #[test]
+    fn test_sudoku_solve_cell() {
+        let s = presets::load_hard().with_standard_rules();
+        let solution = s.solved().unwrap();
+        for &i in &[0, 40, 80] {
+            let want = CellAnswer::Determined(solution.cells[i].value());
+            assert_eq!(s.solve_cell(i, true), want, "i={}", i);
+        }
+        assert!(s.cells[0].value() == 0);
+
+        // The rules alone get stuck on this one.
+        let s = presets::load_expert().with_standard_rules();
+        let mut stuck = s.duplicate();
+        stuck.solve();
+        let i = stuck.most_constrained().unwrap();
+        assert!(matches!(s.solve_cell(i, false), CellAnswer::Ambiguous(_)));
+        let want = CellAnswer::Determined(s.solved().unwrap().cells[i].value());
+        assert_eq!(s.solve_cell(i, true), want);
+
+        let empty = Sudoku::default().with_standard_rules();
+        let all: Vec<u8> = (1..=9).collect();
+        assert_eq!(
+            empty.solve_cell(40, false),
+            CellAnswer::Ambiguous(all.clone())
+        );
+        assert_eq!(empty.solve_cell(40, true), CellAnswer::Ambiguous(all));
+
+        let mut broken = presets::load_easy().with_standard_rules();
+        broken.cells[2].set(3);
+        assert_eq!(broken.solve_cell(40, false), CellAnswer::Contradiction);
+        assert_eq!(broken.solve_cell(40, true), CellAnswer::Contradiction);
+    }
+
+    #[test]
+    fn test_sudoku_grid() {
+        let s = presets::load_hard2();
+        let grid = s.to_grid();
+        assert_eq!(grid[4][5], s.cells[41].value());
+        let have = Sudoku::from_grid(grid).unwrap();
+        assert_eq!(have.cells, s.cells);
+        assert_eq!(have.givens, s.givens);
+
+        let mut bad = grid;
+        bad[8][8] = 10;
+        assert!(Sudoku::from_grid(bad).is_err());
+    }
+
+    #[test]
+    fn test_sudoku_value_array() {
+        let s = presets::load_hard();
+        let values = s.to_value_array();
+        assert_eq!(&values[..3], &[0, 0, 7]);
+        let have = Sudoku::try_from(values).unwrap();
+        assert_eq!(have.cells, s.cells);
+        assert_eq!(have.givens, s.givens);
+        assert_eq!(have.to_options_array(), s.to_options_array());
+        assert_eq!(s.to_options_array()[2], 0);
+        assert_eq!(s.to_options_array()[0], s.cells[0].options());
+
+        let have = Sudoku::try_from_slice(&values[..]).unwrap();
+        assert_eq!(have.cells, s.cells);
+        assert_eq!(
+            Sudoku::try_from_slice(&values[..80]).err(),
+            Some(ValuesError::Length(80))
+        );
+
+        let mut bad = values;
+        bad[40] = 10;
+        bad[41] = 11;
+        assert_eq!(Sudoku::try_from(bad).err(), Some(ValuesError::Value(40)));
+    }
+
     #[test]
     fn test_sudoku_parse() {
         let s: Sudoku = "
@@ -0,0 +1,457 @@
+use crate::random::Rng;
+use crate::structure::{Cell, Sudoku};
+use alloc::vec::Vec;
+
+// +-----------+
+// | Geometric |
+// +-----------+
+
+impl Sudoku {
+    // New board with cell (r, c) taken from cell f(r, c) of this one,
+    // options and givens included.  Rules are copied as they are, so
+    // rules tied to particular cells, like cages, won't follow.
+    fn remap(&self, f: impl Fn(usize, usize) -> (usize, usize)) -> Sudoku {
+        let mut s = self.duplicate();
+        for r in 0..9 {
+            for c in 0..9 {
+                let (sr, sc) = f(r, c);
+                s.cells[r * 9 + c] = self.cells[sr * 9 + sc];
+                s.givens[r * 9 + c] = self.givens[sr * 9 + sc];
+            }
+        }
+        s
+    }
+
+    /// Turned a quarter clockwise: the top row becomes the right
+    /// column.
+    pub fn rotate90(&self) -> Sudoku {
+        self.remap(|r, c| (8 - c, r))
+    }
+
+    /// Mirrored left to right.
+    pub fn reflect_horizontal(&self) -> Sudoku {
+        self.remap(|r, c| (r, 8 - c))
+    }
+
+    /// Mirrored top to bottom.
+    pub fn reflect_vertical(&self) -> Sudoku {
+        self.remap(|r, c| (8 - r, c))
+    }
+
+    /// Mirrored along the main diagonal, rows become columns.
+    pub fn transpose(&self) -> Sudoku {
+        self.remap(|r, c| (c, r))
+    }
+}
+
+// +-------+
+// | Lines |
+// +-------+
+
+impl Sudoku {
+    fn swap_cells(&mut self, a: usize, b: usize) {
+        self.cells.swap(a, b);
+        self.givens.swap(a, b);
+    }
+
+    /// Swap two rows of the same band.  Panics on rows of different
+    /// bands, which would break the squares.
+    pub fn swap_rows(&mut self, a: usize, b: usize) {
+        assert!(a < 9 && b < 9 && a / 3 == b / 3, "a={} b={}", a, b);
+        for c in 0..9 {
+            self.swap_cells(a * 9 + c, b * 9 + c);
+        }
+    }
+
+    /// Swap two columns of the same stack, see swap_rows().
+    pub fn swap_cols(&mut self, a: usize, b: usize) {
+        assert!(a < 9 && b < 9 && a / 3 == b / 3, "a={} b={}", a, b);
+        for r in 0..9 {
+            self.swap_cells(r * 9 + a, r * 9 + b);
+        }
+    }
+
+    /// Swap two bands of three rows, 0 being the top one.
+    pub fn swap_bands(&mut self, a: usize, b: usize) {
+        assert!(a < 3 && b < 3, "a={} b={}", a, b);
+        if a != b {
+            for k in 0..3 {
+                for c in 0..9 {
+                    self.swap_cells((a * 3 + k) * 9 + c, (b * 3 + k) * 9 + c);
+                }
+            }
+        }
+    }
+
+    /// Swap two stacks of three columns, 0 being the left one.
+    pub fn swap_stacks(&mut self, a: usize, b: usize) {
+        assert!(a < 3 && b < 3, "a={} b={}", a, b);
+        if a != b {
+            for k in 0..3 {
+                for r in 0..9 {
+                    self.swap_cells(r * 9 + a * 3 + k, r * 9 + b * 3 + k);
+                }
+            }
+        }
+    }
+}
+
+// +--------+
+// | Digits |
+// +--------+
+
+impl Sudoku {
+    /// Same board with every value and option v replaced by
+    /// perm[v - 1].  Panics if perm isn't a permutation of 1-9.
+    pub fn relabel(&self, perm: [u8; 9]) -> Sudoku {
+        let mut sorted = perm;
+        sorted.sort_unstable();
+        assert!(sorted == [1, 2, 3, 4, 5, 6, 7, 8, 9], "perm={:?}", perm);
+
+        let mut s = self.duplicate();
+        for (x, cell) in s.cells.iter_mut().zip(self.cells.iter()) {
+            *x = Cell::default();
+            if cell.value() != 0 {
+                x.set(perm[cell.value() as usize - 1]);
+                continue;
+            }
+            for v in (1u8..=9).filter(|v| !cell.is_open(*v)) {
+                x.shut(perm[v as usize - 1]);
+            }
+        }
+        s
+    }
+}
+
+// +---------+
+// | Shuffle |
+// +---------+
+
+impl Sudoku {
+    /// Scramble the board with a random relabeling, rotation and a
+    /// few band, stack, row and column swaps.  None of those break a
+    /// valid board, so a solution stays a solution, just a different
+    /// looking one.  Draws that happen to undo each other are thrown
+    /// away, only a board without any values comes out the same.
+    pub fn shuffle_solution(&mut self, rng: &mut Rng) {
+        if self.cells.iter().all(|x| x.value() == 0) {
+            return;
+        }
+        loop {
+            let s = self.shuffled(rng);
+            if !s.same_values(self) {
+                *self = s;
+                return;
+            }
+        }
+    }
+
+    // One draw of shuffle_solution().
+    fn shuffled(&self, rng: &mut Rng) -> Sudoku {
+        let mut perm = [1, 2, 3, 4, 5, 6, 7, 8, 9];
+        rng.shuffle(&mut perm);
+        let mut s = self.relabel(perm);
+        for _ in 0..rng.below(4) {
+            s = s.rotate90();
+        }
+        for _ in 0..12 {
+            let (a, b) = (rng.below(3), rng.below(3));
+            let base = rng.below(3) * 3;
+            match rng.below(4) {
+                0 => s.swap_bands(a, b),
+                1 => s.swap_stacks(a, b),
+                2 => s.swap_rows(base + a, base + b),
+                _ => s.swap_cols(base + a, base + b),
+            }
+        }
+        s
+    }
+}
+
+// +-----------+
+// | Canonical |
+// +-----------+
+
+// The 1296 orders of nine lines that keep bands together: the bands
+// in any order, and the lines of each band in any order.
+fn line_orders() -> Vec<[usize; 9]> {
+    const ORDERS: [[usize; 3]; 6] = [
+        [0, 1, 2],
+        [0, 2, 1],
+        [1, 0, 2],
+        [1, 2, 0],
+        [2, 0, 1],
+        [2, 1, 0],
+    ];
+    let mut xs = Vec::with_capacity(1296);
+    for bands in ORDERS.iter() {
+        for a in ORDERS.iter() {
+            for b in ORDERS.iter() {
+                for c in ORDERS.iter() {
+                    let mut x = [0; 9];
+                    for (k, lines) in [a, b, c].iter().enumerate() {
+                        for j in 0..3 {
+                            x[k * 3 + j] = bands[k] * 3 + lines[j];
+                        }
+                    }
+                    xs.push(x);
+                }
+            }
+        }
+    }
+    xs
+}
+
+// Depth first search over the row orders of a grid whose columns are
+// already in place, keeping the smallest grid found in best.  Digits
+// are renamed in order of first appearance, which only depends on
+// the rows placed so far, so a prefix already larger than the same
+// rows of best can be dropped.
+struct Search {
+    grid: [u8; 81],
+    best: [u8; 81],
+    rows: [usize; 9],
+    used: [bool; 9],
+}
+
+impl Search {
+    fn run(&mut self, k: usize, names: [u8; 10], next: u8, out: &mut [u8; 81]) {
+        if k == 9 {
+            if out[..] < self.best[..] {
+                self.best = *out;
+            }
+            return;
+        }
+        for row in 0..9 {
+            // A new band starts on any row of an untouched band, the
+            // band's other rows follow in any order.
+            let band = row / 3 * 3;
+            let ok = if k % 3 == 0 {
+                !self.used[band..band + 3].iter().any(|x| *x)
+            } else {
+                band == self.rows[k - 1] / 3 * 3 && !self.used[row]
+            };
+            if !ok {
+                continue;
+            }
+            let (mut names, mut next) = (names, next);
+            for c in 0..9 {
+                let v = self.grid[row * 9 + c] as usize;
+                if v != 0 && names[v] == 0 {
+                    names[v] = next;
+                    next += 1;
+                }
+                out[k * 9 + c] = names[v];
+            }
+            if out[..(k + 1) * 9] > self.best[..(k + 1) * 9] {
+                continue;
+            }
+            self.rows[k] = row;
+            self.used[row] = true;
+            self.run(k + 1, names, next, out);
+            self.used[row] = false;
+        }
+    }
+}
+
+impl Sudoku {
+    /// Smallest values grid, read row by row with 0 for empty cells,
+    /// over every transposition, row and column order keeping bands
+    /// and stacks together, and renaming of digits.  Boards with the
+    /// same canonical form only differ by those symmetries.
+    pub fn canonical(&self) -> [u8; 81] {
+        let values: Vec<u8> = self.cells.iter().map(|x| x.value()).collect();
+        let mut search = Search {
+            grid: [0; 81],
+            best: [10; 81],
+            rows: [0; 9],
+            used: [false; 9],
+        };
+        for transpose in [false, true].iter() {
+            for cols in line_orders() {
+                for r in 0..9 {
+                    for (c, &col) in cols.iter().enumerate() {
+                        let (sr, sc) = if *transpose { (col, r) } else { (r, col) };
+                        search.grid[r * 9 + c] = values[sr * 9 + sc];
+                    }
+                }
+                search.run(0, [0; 10], 1, &mut [0; 81]);
+            }
+        }
+        search.best
+    }
+
+    /// Same givens up to rotation, reflection, band and stack
+    /// permutations and renaming of digits.
+    pub fn is_equivalent(&self, other: &Sudoku) -> bool {
+        self.canonical() == other.canonical()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::presets;
+
+    #[test]
+    fn test_rotate90() {
+        let s = presets::load_easy();
+        let once = s.rotate90();
+        assert_eq!(s.cells[0].value(), 3);
+        assert_eq!(once.cells[8].value(), 3);
+        assert!(once.givens[8] && !once.givens[0]);
+        // r1c2 holds a 4 and goes to r2c9.
+        assert_eq!(once.cells[17].value(), 4);
+        // Unsolved r1c3 keeps its options at r3c9.
+        assert_eq!(once.cells[26], s.cells[2]);
+
+        let back = once.rotate90().rotate90().rotate90();
+        assert_eq!(back.cells, s.cells);
+        assert_eq!(back.givens, s.givens);
+        assert!(!once.same_values(&s));
+    }
+
+    #[test]
+    fn test_reflections() {
+        let s = presets::load_hard();
+        assert_eq!(s.reflect_horizontal().cells[6].value(), 7);
+        assert_eq!(s.reflect_vertical().cells[74].value(), 7);
+        assert_eq!(s.transpose().cells[18].value(), 7);
+        for x in [s.reflect_horizontal(), s.reflect_vertical(), s.transpose()].iter() {
+            assert!(!x.same_values(&s));
+            assert!(x.is_equivalent(&s));
+        }
+        assert_eq!(s.reflect_horizontal().reflect_horizontal().cells, s.cells);
+        assert_eq!(s.transpose().transpose().cells, s.cells);
+        // Both mirrors make a half turn.
+        let half = s.rotate90().rotate90();
+        assert_eq!(s.reflect_horizontal().reflect_vertical().cells, half.cells);
+    }
+
+    #[test]
+    fn test_swap_rows() {
+        let solution = Sudoku::random_solved(1);
+        let mut s = solution.duplicate();
+        s.swap_rows(3, 5);
+        assert!(s.is_valid() && s.is_solved());
+        assert!(!s.same_values(&solution));
+        assert_eq!(s.cells[27], solution.cells[45]);
+        assert_eq!(s.cells[36], solution.cells[36]);
+        s.swap_rows(5, 3);
+        assert!(s.same_values(&solution));
+
+        s.swap_cols(6, 8);
+        assert!(s.is_valid());
+        assert_eq!(s.cells[8], solution.cells[6]);
+        assert!(s.is_equivalent(&solution));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_swap_rows_across_bands() {
+        Sudoku::default().swap_rows(2, 3);
+    }
+
+    #[test]
+    fn test_swap_bands() {
+        // The 3 at r1c1 and the 7 at r7c2 trade places.
+        let mut s = presets::load_easy();
+        assert_eq!((s.cells[0].value(), s.cells[55].value()), (3, 7));
+        s.swap_bands(0, 2);
+        assert_eq!((s.cells[54].value(), s.cells[1].value()), (3, 7));
+        assert!(s.givens[54] && !s.givens[0]);
+        assert!(s.is_valid());
+
+        s.swap_stacks(0, 1);
+        assert_eq!(s.cells[57].value(), 3);
+        s.swap_stacks(1, 0);
+        s.swap_bands(2, 0);
+        assert_eq!(s.cells, presets::load_easy().cells);
+    }
+
+    #[test]
+    fn test_shuffle_solution() {
+        let mut rng = Rng::new(5);
+        for seed in 0..10 {
+            let solution = Sudoku::random_solved(seed);
+            let mut s = solution.duplicate();
+            s.shuffle_solution(&mut rng);
+            assert!(s.is_solved() && s.is_valid(), "seed={}", seed);
+            assert!(!s.same_values(&solution), "seed={}", seed);
+            assert!(s.is_equivalent(&solution), "seed={}", seed);
+        }
+
+        // A single given is easy to land back on.
+        let mut one = Sudoku::default();
+        one.cells[0].set(1);
+        for _ in 0..100 {
+            let mut s = one.duplicate();
+            s.shuffle_solution(&mut rng);
+            assert!(!s.same_values(&one));
+        }
+        let mut empty = Sudoku::default();
+        empty.shuffle_solution(&mut rng);
+        assert!(empty.same_values(&Sudoku::default()));
+    }
+
+    #[test]
+    fn test_relabel() {
+        let s = presets::load_easy();
+        let same = s.relabel([1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(same.cells, s.cells);
+        assert_eq!(same.givens, s.givens);
+
+        let mut s = s;
+        s.cells[2].shut(1);
+        let x = s.relabel([2, 1, 3, 4, 5, 6, 7, 8, 9]);
+        for i in 0..81 {
+            let want = match s.cells[i].value() {
+                1 => 2,
+                2 => 1,
+                v => v,
+            };
+            assert_eq!(x.cells[i].value(), want, "i={}", i);
+        }
+        // r4c2 holds a 1, r9c4 a 2.
+        assert_eq!(x.cells[28].value(), 2);
+        assert_eq!(x.cells[75].value(), 1);
+        assert!(x.cells[2].is_open(1) && !x.cells[2].is_open(2));
+        assert!(x.is_valid());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_relabel_not_a_permutation() {
+        presets::load_easy().relabel([1, 1, 3, 4, 5, 6, 7, 8, 9]);
+    }
+
+    #[test]
+    fn test_line_orders() {
+        let xs = line_orders();
+        assert_eq!(xs.len(), 1296);
+        assert_eq!(xs[0], [0, 1, 2, 3, 4, 5, 6, 7, 8]);
+        for x in xs.iter() {
+            let mut sorted = *x;
+            sorted.sort_unstable();
+            assert_eq!(sorted, [0, 1, 2, 3, 4, 5, 6, 7, 8]);
+            assert!((0..9).all(|i| x[i] / 3 == x[i / 3 * 3] / 3));
+        }
+    }
+
+    #[test]
+    fn test_is_equivalent() {
+        let s = presets::load_easy();
+        // Rotate 90 degrees clockwise and swap the digits 1 and 2.
+        let other = s.rotate90().relabel([2, 1, 3, 4, 5, 6, 7, 8, 9]);
+        assert!(!other.same_values(&s));
+        assert_eq!(s.canonical(), other.canonical());
+        assert!(s.is_equivalent(&other));
+
+        // A single extra clue breaks it.
+        let mut x = other.duplicate();
+        let empty = (0..81).find(|i| x.cells[*i].value() == 0).unwrap();
+        x.cells[empty].set(9);
+        assert!(!s.is_equivalent(&x));
+        assert!(!s.is_equivalent(&presets::load_hard()));
+    }
+}
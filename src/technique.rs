@@ -0,0 +1,208 @@
+use crate::rules::{
+    ExclusionRule, JellyfishRule, NakedPairRule, SingleOptRule, SwordfishRule, XWingRule,
+};
+use crate::structure::{Progress, Rule, Solver, Sudoku};
+use alloc::{boxed::Box, vec, vec::Vec};
+use core::fmt::{Display, Error, Formatter};
+
+/// Solving techniques of classic sudoku, simplest first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Technique {
+    // Exclusion, a cell left with a single option.
+    NakedSingle,
+    // Value with a single place left in a unit.
+    HiddenSingle,
+    // Two cells of a unit sharing the same two options.
+    NakedPair,
+    XWing,
+    Swordfish,
+    Jellyfish,
+    // Guessing, the rules alone get stuck.
+    Search,
+}
+
+impl Technique {
+    /// All of them, simplest first.
+    pub const ALL: [Technique; 7] = [
+        Technique::NakedSingle,
+        Technique::HiddenSingle,
+        Technique::NakedPair,
+        Technique::XWing,
+        Technique::Swordfish,
+        Technique::Jellyfish,
+        Technique::Search,
+    ];
+
+    /// Rules implementing the technique, none for search.
+    pub fn rules(&self) -> Vec<Box<dyn Rule>> {
+        match self {
+            Technique::NakedSingle => vec![
+                Box::new(ExclusionRule::new_row()),
+                Box::new(ExclusionRule::new_col()),
+                Box::new(ExclusionRule::new_square()),
+            ],
+            Technique::HiddenSingle => vec![
+                Box::new(SingleOptRule::new_row()),
+                Box::new(SingleOptRule::new_col()),
+                Box::new(SingleOptRule::new_square()),
+            ],
+            Technique::NakedPair => vec![
+                Box::new(NakedPairRule::new_row()),
+                Box::new(NakedPairRule::new_col()),
+                Box::new(NakedPairRule::new_square()),
+            ],
+            Technique::XWing => vec![
+                Box::new(XWingRule::new_row()),
+                Box::new(XWingRule::new_col()),
+            ],
+            Technique::Swordfish => vec![
+                Box::new(SwordfishRule::new_row()),
+                Box::new(SwordfishRule::new_col()),
+            ],
+            Technique::Jellyfish => vec![
+                Box::new(JellyfishRule::new_row()),
+                Box::new(JellyfishRule::new_col()),
+            ],
+            Technique::Search => vec![],
+        }
+    }
+}
+
+impl Display for Technique {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        let name = match self {
+            Technique::NakedSingle => "naked single",
+            Technique::HiddenSingle => "hidden single",
+            Technique::NakedPair => "naked pair",
+            Technique::XWing => "x-wing",
+            Technique::Swordfish => "swordfish",
+            Technique::Jellyfish => "jellyfish",
+            Technique::Search => "search",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Sudoku {
+    /// Fraction of cells solved, from 0.0 to 1.0.
+    pub fn completion(&self) -> f32 {
+        self.num_solved() as f32 / 81.0
+    }
+
+    /// Most advanced technique a classic solve of the board needs.
+    /// Techniques are brought in one at a time, simplest first, on a
+    /// duplicate without the board's own rules, and the one in use
+    /// when the board gets solved is the answer.  Boards the rules
+    /// can't finish, or can't solve at all, need search.
+    pub fn hardest_technique_used(&self) -> Technique {
+        let mut s = self.duplicate();
+        s.rules.clear();
+        for technique in Technique::ALL.iter() {
+            s.rules.extend(technique.rules());
+            s.solve();
+            if s.is_solved() {
+                return *technique;
+            }
+        }
+        Technique::Search
+    }
+
+    /// Replace the rules with those of the techniques up to max and
+    /// solve as far as they get.  Search, if allowed, finishes off
+    /// whatever the rules leave, a board it can't finish either stays
+    /// stuck.
+    pub fn solve_up_to(&mut self, max: Technique) -> Progress {
+        self.rules = Technique::ALL
+            .iter()
+            .filter(|x| **x <= max)
+            .flat_map(|x| x.rules())
+            .collect();
+        let progress = self.solve2();
+        if let (Progress::Stuck { .. }, Technique::Search) = (progress, max) {
+            if let Some(x) = self.solved() {
+                self.cells = x.cells;
+                return Progress::Solved;
+            }
+        }
+        progress
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::presets;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_completion() {
+        let s = presets::load_easy();
+        assert_eq!(s.completion(), s.num_solved() as f32 / 81.0);
+        assert_eq!(Sudoku::default().completion(), 0.0);
+        assert_eq!(Sudoku::random_solved(1).completion(), 1.0);
+    }
+
+    // Singles get stuck on this one until an x-wing clears the way.
+    const X_WING: &str = "
+        1 0 0 0 0 0 5 6 9
+        4 9 2 0 5 6 1 0 8
+        0 5 6 1 0 9 2 4 0
+        0 0 9 6 4 0 8 0 1
+        0 6 4 0 1 0 0 0 0
+        2 1 8 0 3 5 6 0 4
+        0 4 0 5 0 0 0 1 6
+        9 0 5 0 6 1 4 0 2
+        6 2 1 0 0 0 0 0 5";
+
+    #[test]
+    fn test_hardest_technique_x_wing() {
+        let s: Sudoku = X_WING.parse().unwrap();
+        assert_eq!(s.hardest_technique_used(), Technique::XWing);
+
+        let mut stuck = s.duplicate().with_standard_rules();
+        stuck.solve();
+        assert!(!stuck.is_solved());
+    }
+
+    #[test]
+    fn test_solve_up_to() {
+        let mut s: Sudoku = X_WING.parse().unwrap();
+        let clues = s.num_solved() as u32;
+        match s.solve_up_to(Technique::NakedPair) {
+            Progress::Stuck { solved } => {
+                assert_eq!(s.num_solved() as u32, clues + solved);
+                assert!(!s.is_solved());
+            }
+            x => panic!("{:?}", x),
+        }
+        assert_eq!(s.rules.len(), 9);
+        assert!(s.is_valid());
+
+        // Picks up where naked pairs left off.
+        assert_eq!(s.solve_up_to(Technique::XWing), Progress::Solved);
+
+        let mut s = presets::load_expert();
+        assert!(matches!(
+            s.solve_up_to(Technique::Jellyfish),
+            Progress::Stuck { .. }
+        ));
+        assert_eq!(s.solve_up_to(Technique::Search), Progress::Solved);
+        assert!(s.is_valid());
+    }
+
+    #[test]
+    fn test_hardest_technique_used() {
+        let s = presets::load_easy();
+        assert_eq!(s.hardest_technique_used(), Technique::NakedSingle);
+        // Nothing left to do takes nothing more than the simplest.
+        assert_eq!(
+            Sudoku::random_solved(1).hardest_technique_used(),
+            Technique::NakedSingle
+        );
+        assert_eq!(
+            presets::load_expert().hardest_technique_used(),
+            Technique::Search
+        );
+        assert_eq!(Technique::XWing.to_string(), "x-wing");
+    }
+}
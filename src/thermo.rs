@@ -0,0 +1,152 @@
+use crate::comparison::InequalityRule;
+use crate::structure::{parse_cell, Sudoku};
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+#[derive(Debug, PartialEq)]
+pub enum ThermoError {
+    // Token that isn't a valid cell reference.
+    Parse(String),
+    // Cell index outside of the grid.
+    OutOfRange(usize),
+    // Thermometers are 2 to 9 cells long.
+    Length(usize),
+    // Cell visited twice.
+    Repeat(usize),
+    // Consecutive cells that don't touch, not even diagonally.
+    NotConnected(usize, usize),
+}
+
+/// Parse one thermometer per line, bulb first, e.g. "r1c1 r2c2 r2c3".
+pub fn parse_thermometers(s: &str) -> Result<Vec<Vec<usize>>, ThermoError> {
+    s.lines()
+        .filter(|x| !x.trim().is_empty())
+        .map(|line| {
+            line.split_whitespace()
+                .map(|x| parse_cell(x).ok_or_else(|| ThermoError::Parse(x.to_string())))
+                .collect()
+        })
+        .collect()
+}
+
+fn touching(a: usize, b: usize) -> bool {
+    let (a, b) = (a as i32, b as i32);
+    let dr = (a / 9 - b / 9).abs();
+    let dc = (a % 9 - b % 9).abs();
+    a != b && dr <= 1 && dc <= 1
+}
+
+impl Sudoku {
+    /// Values along a thermometer strictly increase from the bulb,
+    /// the first cell.
+    ///
+    /// The k-th cell of a thermometer of length L is bound to
+    /// k+1..=9-(L-1-k) right away, while tighter bounds coming from
+    /// its neighbours are left to a pair of inequality rules between
+    /// each two consecutive cells.
+    pub fn add_thermometer(&mut self, cells: Vec<usize>) -> Result<(), ThermoError> {
+        let n = cells.len();
+        if !(2..=9).contains(&n) {
+            return Err(ThermoError::Length(n));
+        }
+        for (k, &index) in cells.iter().enumerate() {
+            if index >= 81 {
+                return Err(ThermoError::OutOfRange(index));
+            }
+            if cells[..k].contains(&index) {
+                return Err(ThermoError::Repeat(index));
+            }
+            if k > 0 && !touching(cells[k - 1], index) {
+                return Err(ThermoError::NotConnected(cells[k - 1], index));
+            }
+        }
+
+        for (k, &index) in cells.iter().enumerate() {
+            let (lo, hi) = (k as u8 + 1, (9 - (n - 1 - k)) as u8);
+            self.cells[index].shut_outside(lo, hi);
+        }
+        for x in cells.windows(2) {
+            self.rules.push(Box::new(InequalityRule::less(x[0], x[1])));
+            self.rules
+                .push(Box::new(InequalityRule::greater(x[1], x[0])));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::presets;
+    use crate::structure::Solver;
+
+    #[test]
+    fn test_parse_thermometers() {
+        let have = parse_thermometers("r1c1 r2c2\n\nr9c9 r9c8 r8c7\n").unwrap();
+        assert_eq!(have, vec![vec![0, 10], vec![80, 79, 69]]);
+        assert!(parse_thermometers("r1c1 r0c1").is_err());
+    }
+
+    #[test]
+    fn test_add_thermometer_invalid() {
+        let mut s = Sudoku::default();
+        assert_eq!(s.add_thermometer(vec![0]), Err(ThermoError::Length(1)));
+        assert_eq!(
+            s.add_thermometer((0..10).collect()),
+            Err(ThermoError::Length(10))
+        );
+        assert_eq!(
+            s.add_thermometer(vec![0, 1, 0]),
+            Err(ThermoError::Repeat(0))
+        );
+        assert_eq!(
+            s.add_thermometer(vec![0, 2]),
+            Err(ThermoError::NotConnected(0, 2))
+        );
+        assert_eq!(
+            s.add_thermometer(vec![8, 9]),
+            Err(ThermoError::NotConnected(8, 9))
+        );
+        assert_eq!(
+            s.add_thermometer(vec![80, 81]),
+            Err(ThermoError::OutOfRange(81))
+        );
+        assert!(s.rules.is_empty());
+        assert_eq!(s.add_thermometer(vec![0, 10, 20]), Ok(()));
+    }
+
+    #[test]
+    fn test_thermometer_length_9() {
+        let mut s = Sudoku::default();
+        let path = vec![0, 10, 20, 30, 40, 50, 60, 70, 80];
+        s.add_thermometer(path.clone()).unwrap();
+        for (k, &index) in path.iter().enumerate() {
+            assert_eq!(s.cells[index].num_options(), 1);
+            assert!(s.cells[index].is_open(k as u8 + 1));
+        }
+        assert_eq!(s.solve(), 9);
+        for (k, &index) in path.iter().enumerate() {
+            assert_eq!(s.cells[index].value(), k as u8 + 1);
+        }
+    }
+
+    #[test]
+    fn test_thermometer_length_5() {
+        let mut s = Sudoku::default();
+        s.add_thermometer(vec![0, 1, 2, 3, 4]).unwrap();
+        assert_eq!(s.cells[0].options(), 0b0_0001_1111);
+        assert_eq!(s.cells[2].options(), 0b0_0111_1100);
+        assert_eq!(s.cells[4].options(), 0b1_1111_0000);
+        assert_eq!(s.cells[5].num_options(), 9);
+    }
+
+    #[test]
+    fn test_thermo() {
+        let mut s = presets::load_thermo().with_standard_rules();
+        s.solve();
+        assert!(s.is_solved());
+    }
+}
@@ -0,0 +1,299 @@
+// wasm-bindgen surface for a browser front end, strings in and out.
+// Input comes straight from the page, so nothing here may panic: bad
+// lines come back as None, null, "invalid" or "".  Should a bug panic
+// anyway, the message goes to the console with the JS error.
+//
+// The functions are plain Rust too, so the native tests cover them;
+// `wasm-pack test --node --features wasm` runs the wasm ones.
+
+use crate::presets::Difficulty;
+use crate::random::Rng;
+use crate::rules::{ExclusionRule, SingleOptRule};
+use crate::structure::{ChangeKind, PassObserver, Rule, Solver, StepResult, Sudoku};
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::convert::TryFrom;
+use wasm_bindgen::prelude::*;
+
+/// Runs when the module loads.
+#[wasm_bindgen(start)]
+pub fn start() {
+    console_error_panic_hook::set_once();
+}
+
+// Puzzle out of an 81 character line, rejecting repeated givens.
+fn parse(line: &str) -> Option<Sudoku> {
+    let s: Sudoku = line.parse().ok()?;
+    if s.is_valid() {
+        Some(s)
+    } else {
+        None
+    }
+}
+
+/// Solution of an 81 character puzzle as 81 digits, or None if the
+/// line isn't a puzzle or the puzzle has no solution.
+#[wasm_bindgen]
+pub fn solve(line: &str) -> Option<String> {
+    let s = parse(line)?.with_standard_rules();
+    s.solved().map(|x| x.to_line())
+}
+
+/// Next cell the standard rules can fill in, as handed to JS.
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hint {
+    pub index: usize,
+    pub row: usize,
+    pub col: usize,
+    pub value: u8,
+    // Name of the rule that left the value, e.g. "single_opt_row".
+    pub rule: String,
+}
+
+// First cell placed by the first pass of the standard rules that
+// places any, in reading order.  The rule is the one that set the
+// value, or else the last one to shut an option of the cell.
+fn next_hint(line: &str) -> Option<Hint> {
+    let mut s = parse(line)?.with_standard_rules();
+    let mut first = FirstPlaced(None);
+    s.passes_with(&mut first)
+        .take_while(|x| x.placed == 0)
+        .for_each(drop);
+    first.0
+}
+
+struct FirstPlaced(Option<Hint>);
+
+impl PassObserver for FirstPlaced {
+    fn passed(&mut self, sudoku: &Sudoku, step: &StepResult) {
+        if self.0.is_some() {
+            return;
+        }
+        let index = match step.changed.iter().find(|i| sudoku.cells[**i].value() != 0) {
+            Some(&x) => x,
+            None => return,
+        };
+        let changed = |rule: &dyn Rule, set: bool| {
+            rule.last_changes()
+                .iter()
+                .any(|c| c.index == index && (!set || matches!(c.kind, ChangeKind::Set(_))))
+        };
+        let set = sudoku.rules.iter().find(|x| changed(x.as_ref(), true));
+        let shut = sudoku
+            .rules
+            .iter()
+            .rev()
+            .find(|x| changed(x.as_ref(), false));
+        self.0 = Some(Hint {
+            index,
+            row: index / 9,
+            col: index % 9,
+            value: sudoku.cells[index].value(),
+            rule: set.or(shut).map_or("", |x| x.name()).to_string(),
+        });
+    }
+}
+
+/// Next hint for an 81 character puzzle as an object with index,
+/// row, col and value, or null if the line isn't a puzzle or the
+/// rules can't place anything.
+#[wasm_bindgen]
+pub fn hint(line: &str) -> JsValue {
+    next_hint(line).map_or(JsValue::NULL, JsValue::from)
+}
+
+// Graded like the collection in presets: Easy if exclusion alone
+// solves it, Medium if single options are needed too and Hard if the
+// rules get stuck.  None if there's no solution.
+fn difficulty(s: Sudoku) -> Option<Difficulty> {
+    s.duplicate().with_standard_rules().solved()?;
+    let mut s = s;
+    s.rules.push(Box::new(ExclusionRule::new_row()));
+    s.rules.push(Box::new(ExclusionRule::new_col()));
+    s.rules.push(Box::new(ExclusionRule::new_square()));
+    s.solve();
+    if s.is_solved() {
+        return Some(Difficulty::Easy);
+    }
+    s.rules.push(Box::new(SingleOptRule::new_row()));
+    s.rules.push(Box::new(SingleOptRule::new_col()));
+    s.rules.push(Box::new(SingleOptRule::new_square()));
+    s.solve();
+    if s.is_solved() {
+        Some(Difficulty::Medium)
+    } else {
+        Some(Difficulty::Hard)
+    }
+}
+
+fn difficulty_name(d: Difficulty) -> &'static str {
+    match d {
+        Difficulty::Easy => "easy",
+        Difficulty::Medium => "medium",
+        Difficulty::Hard => "hard",
+    }
+}
+
+/// Difficulty of an 81 character puzzle, "easy", "medium" or
+/// "hard".  Lines that aren't solvable puzzles are "invalid".
+#[wasm_bindgen]
+pub fn grade(line: &str) -> String {
+    parse(line)
+        .and_then(difficulty)
+        .map_or("invalid", difficulty_name)
+        .to_string()
+}
+
+// Grids generate() tries before giving up.
+const MAX_ATTEMPTS: u64 = 50;
+
+/// Puzzle with a unique solution of the given difficulty, "easy",
+/// "medium" or "hard", as 81 characters.  The same seed gives the
+/// same puzzle.  Any other difficulty, or no puzzle found within 50
+/// grids, gives "".
+#[wasm_bindgen]
+pub fn generate(seed: u32, difficulty_wanted: &str) -> String {
+    let want = match difficulty_wanted {
+        "easy" => Difficulty::Easy,
+        "medium" => Difficulty::Medium,
+        "hard" => Difficulty::Hard,
+        _ => return String::new(),
+    };
+    generate_within(seed, want, MAX_ATTEMPTS).unwrap_or_default()
+}
+
+// A minimal puzzle is as hard as its grid gets, values of the
+// solution go back in until it's no harder than wanted.  Grids too
+// easy for hard are skipped, up to the given number of them.
+fn generate_within(seed: u32, want: Difficulty, attempts: u64) -> Option<String> {
+    for attempt in 0..attempts {
+        let seed = (seed as u64) << 32 | attempt;
+        let solution = Sudoku::random_solved(seed);
+        let mut puzzle = solution.masked(seed, 81).with_standard_rules();
+        puzzle.minimize();
+        let mut values = puzzle.to_value_array();
+        let mut order: Vec<usize> = (0..81).filter(|i| values[*i] == 0).collect();
+        Rng::new(seed).shuffle(&mut order);
+        for k in 0..=order.len() {
+            if k > 0 {
+                values[order[k - 1]] = solution.cells[order[k - 1]].value();
+            }
+            let s = match Sudoku::try_from(values) {
+                Ok(s) => s,
+                Err(_) => break,
+            };
+            match difficulty(s.duplicate()) {
+                Some(d) if d == want => return Some(s.to_line()),
+                Some(d) if (d as u8) < (want as u8) => break,
+                _ => (),
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::presets;
+    use alloc::vec;
+
+    #[test]
+    fn test_solve() {
+        for entry in presets::collection().iter().take(3) {
+            let line = entry.puzzle.to_line();
+            assert_eq!(solve(&line), Some(entry.solution.to_line()));
+        }
+    }
+
+    #[test]
+    fn test_solve_malformed() {
+        assert_eq!(solve(""), None);
+        assert_eq!(solve("123"), None);
+        assert_eq!(solve(&"1".repeat(81)), None);
+        assert_eq!(solve(&"x".repeat(200)), None);
+    }
+
+    #[test]
+    fn test_hint() {
+        let easy = presets::load_easy();
+        let h = next_hint(&easy.to_line()).unwrap();
+        let solution = easy.with_standard_rules().solved().unwrap();
+        assert_eq!(solution.cells[h.index].value(), h.value);
+        // The square of r1c7 shuts the last of its options but 8.
+        let want = Hint {
+            index: 6,
+            row: 0,
+            col: 6,
+            value: 8,
+            rule: "exclusion_square".to_string(),
+        };
+        assert_eq!(h, want);
+
+        assert_eq!(next_hint(&solution.to_line()), None);
+        assert_eq!(next_hint("123"), None);
+        assert_eq!(next_hint(&"1".repeat(81)), None);
+    }
+
+    #[test]
+    fn test_grade() {
+        for entry in presets::collection() {
+            let want = difficulty_name(entry.difficulty);
+            assert_eq!(grade(&entry.puzzle.to_line()), want, "name={}", entry.name);
+        }
+        assert_eq!(grade("123"), "invalid");
+        assert_eq!(grade(&"1".repeat(81)), "invalid");
+    }
+
+    #[test]
+    fn test_generate() {
+        let mut lines = vec![];
+        for want in ["easy", "medium", "hard"] {
+            let line = generate(7, want);
+            assert_eq!(grade(&line), want);
+            assert!(parse(&line).unwrap().has_unique_solution());
+            lines.push(line);
+        }
+        assert_eq!(generate(7, "easy"), lines[0]);
+        assert_eq!(generate(7, "impossible"), "");
+        // Seed 7 needs a second grid for hard, so one attempt
+        // gives up.
+        assert_eq!(generate_within(7, Difficulty::Hard, 1), None);
+    }
+}
+
+// Same checks through the JS boundary.
+#[cfg(all(test, target_arch = "wasm32"))]
+mod wasm_tests {
+    use super::*;
+    use crate::presets;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test]
+    fn test_solve() {
+        let entry = &presets::collection()[0];
+        assert_eq!(
+            solve(&entry.puzzle.to_line()),
+            Some(entry.solution.to_line())
+        );
+        assert_eq!(solve("123"), None);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_hint() {
+        assert!(hint(&presets::load_easy().to_line()).is_object());
+        assert!(hint("123").is_null());
+        assert!(hint(&"x".repeat(200)).is_null());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_grade_and_generate() {
+        assert_eq!(grade(&generate(1, "medium")), "medium");
+        assert_eq!(grade("123"), "invalid");
+        assert_eq!(generate(1, ""), "");
+    }
+}